@@ -0,0 +1,40 @@
+//! Captures build information for `--version`: the git commit being built and the
+//! ethers version resolved in Cargo.lock.
+
+use std::process::Command;
+
+fn main() {
+    // Builds without a git checkout, e.g. in Docker, can pass the commit in themselves
+    println!("cargo:rerun-if-env-changed=GIT_HASH");
+    println!("cargo:rerun-if-changed=.git/HEAD");
+    println!("cargo:rerun-if-changed=.git/index");
+    println!("cargo:rerun-if-changed=Cargo.lock");
+
+    let commit = std::env::var("GIT_HASH").ok().or_else(git_commit).unwrap_or_else(|| "unknown".to_string());
+    println!("cargo:rustc-env=GIT_HASH={}", commit);
+    let ethers = ethers_version().unwrap_or_else(|| "unknown".to_string());
+    println!("cargo:rustc-env=ETHERS_VERSION={}", ethers);
+}
+
+/// The short hash of HEAD, suffixed with `-dirty` when there are uncommitted changes
+fn git_commit() -> Option<String> {
+    let output = Command::new("git").args(["rev-parse", "--short", "HEAD"]).output().ok()?;
+    if !output.status.success() {
+        return None;
+    }
+    let hash = String::from_utf8(output.stdout).ok()?.trim().to_string();
+    let dirty = Command::new("git")
+        .args(["status", "--porcelain", "--untracked-files=no"])
+        .output()
+        .is_ok_and(|status| !status.stdout.is_empty());
+    Some(if dirty { format!("{}-dirty", hash) } else { hash })
+}
+
+/// The version of the `ethers` package in Cargo.lock
+fn ethers_version() -> Option<String> {
+    let lock = std::fs::read_to_string("Cargo.lock").ok()?;
+    let mut lines = lock.lines();
+    lines.find(|line| *line == "name = \"ethers\"")?;
+    let version = lines.next()?.strip_prefix("version = \"")?.strip_suffix('"')?;
+    Some(version.to_string())
+}
@@ -0,0 +1,127 @@
+use crate::{rank_opportunities, Costs, Dex, DexKind, Pair, PairToken, Settings, Thresholds};
+use ethers::types::Address;
+use eyre::Result;
+use rusqlite::Connection;
+use tracing::info;
+
+/// The prices one pair had on every DEX during a single tick
+struct Snapshot {
+    pair: String,
+    amount_in: f64,
+    prices: Vec<(String, f64)>,
+}
+
+/// How the stored history would have fared under one set of thresholds
+#[derive(Debug, Default)]
+struct Summary {
+    opportunities: usize,
+    total_profit: f64,
+}
+
+/// Replays the stored price history at `db_path` through the live detection logic,
+/// once for each threshold, and logs a summary. Never touches the network.
+pub fn run(settings: &Settings, db_path: &str, thresholds: &[f64]) -> Result<()> {
+    let snapshots = load_snapshots(&Connection::open(db_path)?)?;
+    info!("Loaded {} price snapshots from {}", snapshots.len(), db_path);
+
+    let mut spreads = Vec::new();
+    let mut summaries: Vec<Summary> = thresholds.iter().map(|_| Summary::default()).collect();
+    for snapshot in &snapshots {
+        let pair = configured_pair(settings, &snapshot.pair);
+        let dexes: Vec<Dex> = snapshot.prices.iter().map(|(name, _)| configured_dex(settings, name)).collect();
+        let prices: Vec<(&Dex, f64)> = dexes.iter().zip(&snapshot.prices).map(|(dex, (_, price))| (dex, *price)).collect();
+
+        // Only the quotes were stored, which is all the ranking needs: it approximates each
+        // round trip by the sell quote
+        let ranking = rank_opportunities(&pair, snapshot.amount_in, &prices, Costs::new(None, settings));
+        let Some(best) = ranking.first() else {
+            continue;
+        };
+        spreads.push(best.sell_price - best.buy_price);
+
+        for (&threshold, summary) in thresholds.iter().zip(&mut summaries) {
+            let thresholds = Thresholds {
+                minimum_profit: threshold,
+                ..settings.thresholds()
+            };
+            if thresholds.cleared_by(best.net_profit, best.profit_percent) {
+                summary.opportunities += 1;
+                summary.total_profit += best.net_profit;
+            }
+        }
+    }
+
+    if spreads.is_empty() {
+        info!("No snapshots with at least two DEX prices, nothing to backtest.");
+        return Ok(());
+    }
+    let max_spread = spreads.iter().cloned().fold(f64::MIN, f64::max);
+    let avg_spread = spreads.iter().sum::<f64>() / spreads.len() as f64;
+    let decimals = settings.display_decimals;
+    info!("Spread across {} snapshots: max {:.*}, avg {:.*}", spreads.len(), decimals, max_spread, decimals, avg_spread);
+    for (threshold, summary) in thresholds.iter().zip(&summaries) {
+        info!(
+            "Threshold {:.*}: {} opportunities, total hypothetical profit {:.*}",
+            decimals, threshold, summary.opportunities, decimals, summary.total_profit
+        );
+    }
+    Ok(())
+}
+
+/// Groups stored prices into per-tick snapshots. Every price fetched for a pair
+/// at one trade size during one tick shares the same timestamp.
+fn load_snapshots(conn: &Connection) -> Result<Vec<Snapshot>> {
+    let mut stmt =
+        conn.prepare("SELECT timestamp, pair, dex_name, amount_in, amount_out FROM prices ORDER BY timestamp, pair")?;
+    let rows = stmt.query_map([], |row| {
+        Ok((
+            row.get::<_, String>(0)?,
+            row.get::<_, String>(1)?,
+            row.get::<_, String>(2)?,
+            row.get::<_, f64>(3)?,
+            row.get::<_, f64>(4)?,
+        ))
+    })?;
+
+    let mut snapshots: Vec<Snapshot> = Vec::new();
+    let mut current_key: Option<(String, String)> = None;
+    for row in rows {
+        let (timestamp, pair, dex_name, amount_in, amount_out) = row?;
+        let key = (timestamp, pair.clone());
+        if current_key.as_ref() != Some(&key) {
+            snapshots.push(Snapshot { pair, amount_in, prices: Vec::new() });
+            current_key = Some(key);
+        }
+        snapshots.last_mut().unwrap().prices.push((dex_name, amount_out));
+    }
+    Ok(snapshots)
+}
+
+/// The configured DEX with this name, so its fees apply, or a fee-free stand-in
+fn configured_dex(settings: &Settings, name: &str) -> Dex {
+    settings.dexes.iter().find(|dex| dex.name == name).cloned().unwrap_or_else(|| Dex {
+        name: name.to_string(),
+        kind: DexKind::V2,
+        router_address: Address::zero(),
+        fee_tier: None,
+        fee_bps: 0,
+        enabled: true,
+    })
+}
+
+/// The configured pair with this label, or a stand-in for pairs no longer configured
+fn configured_pair(settings: &Settings, label: &str) -> Pair {
+    settings.pairs.iter().find(|pair| pair.label == label).cloned().map(Pair::oriented).unwrap_or_else(|| Pair {
+        label: label.to_string(),
+        token_a: Address::zero(),
+        token_b: Address::zero(),
+        decimals_a: 0,
+        decimals_b: 0,
+        symbol_a: None,
+        symbol_b: None,
+        intermediate_tokens: Vec::new(),
+        profit_in: PairToken::B,
+        invert: false,
+        minimum_profit_threshold: None,
+    })
+}
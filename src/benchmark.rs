@@ -0,0 +1,85 @@
+use std::sync::Mutex;
+use std::time::Duration;
+use tracing::info;
+
+/// Quote timings collected by `--benchmark`, which checks back to back with no
+/// interval to find how often the RPC endpoint allows checking
+#[derive(Debug, Default)]
+pub struct Benchmark {
+    /// How long each quote took, and whether it succeeded
+    fetches: Mutex<Vec<(Duration, bool)>>,
+}
+
+/// What a benchmark run measured
+#[derive(Debug, PartialEq)]
+pub struct Report {
+    pub ticks: u64,
+    pub ticks_per_second: f64,
+    pub average_latency: Option<Duration>,
+    pub p99_latency: Option<Duration>,
+    /// The share of quotes that failed, as a percentage
+    pub error_rate_pct: Option<f64>,
+}
+
+impl Benchmark {
+    pub fn record_fetch(&self, took: Duration, succeeded: bool) {
+        self.fetches.lock().unwrap().push((took, succeeded));
+    }
+
+    /// Summarizes the quotes recorded over `ticks` checks taking `elapsed` in all
+    pub fn report(&self, ticks: u64, elapsed: Duration) -> Report {
+        let fetches = self.fetches.lock().unwrap();
+        let mut latencies: Vec<Duration> = fetches.iter().map(|&(took, _)| took).collect();
+        latencies.sort();
+        let failed = fetches.iter().filter(|&&(_, succeeded)| !succeeded).count();
+        let count = latencies.len();
+        Report {
+            ticks,
+            ticks_per_second: ticks as f64 / elapsed.as_secs_f64().max(f64::EPSILON),
+            average_latency: (count > 0).then(|| latencies.iter().sum::<Duration>() / count as u32),
+            // Nearest rank: the smallest latency at least 99% of quotes were as fast as
+            p99_latency: (count > 0).then(|| latencies[(count * 99).div_ceil(100) - 1]),
+            error_rate_pct: (count > 0).then(|| failed as f64 / count as f64 * 100.0),
+        }
+    }
+}
+
+impl Report {
+    pub fn log(&self) {
+        let millis = |latency: Option<Duration>| match latency {
+            Some(latency) => format!("{}ms", latency.as_millis()),
+            None => "n/a".to_string(),
+        };
+        info!("=== Benchmark ===");
+        info!("  - Checks: {} ({:.2} per second)", self.ticks, self.ticks_per_second);
+        if self.ticks_per_second > 0.0 {
+            info!("  - Seconds per check: {:.2}", 1.0 / self.ticks_per_second);
+        }
+        info!("  - Average quote latency: {}", millis(self.average_latency));
+        info!("  - p99 quote latency: {}", millis(self.p99_latency));
+        match self.error_rate_pct {
+            Some(rate) => info!("  - Quote error rate: {:.1}%", rate),
+            None => info!("  - Quote error rate: n/a"),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn reports_throughput_latency_percentiles_and_errors() {
+        let benchmark = Benchmark::default();
+        assert_eq!(benchmark.report(0, Duration::from_secs(10)).p99_latency, None);
+
+        for millis in 1..=200 {
+            benchmark.record_fetch(Duration::from_millis(millis), millis % 50 != 0);
+        }
+        let report = benchmark.report(20, Duration::from_secs(10));
+        assert_eq!(report.ticks_per_second, 2.0);
+        assert_eq!(report.average_latency, Some(Duration::from_micros(100_500)));
+        assert_eq!(report.p99_latency, Some(Duration::from_millis(198)));
+        assert_eq!(report.error_rate_pct, Some(2.0));
+    }
+}
@@ -0,0 +1,54 @@
+use std::time::Duration;
+
+/// Stops the bot from hammering a dead RPC: once `trip_after` ticks in a row have
+/// failed, each further failure is followed by `cooldown` before the next attempt
+#[derive(Debug)]
+pub struct CircuitBreaker {
+    trip_after: u32,
+    cooldown: Duration,
+    consecutive_failures: u32,
+}
+
+impl CircuitBreaker {
+    pub fn new(trip_after: u32, cooldown: Duration) -> Self {
+        CircuitBreaker {
+            trip_after: trip_after.max(1),
+            cooldown,
+            consecutive_failures: 0,
+        }
+    }
+
+    /// Closes the breaker again after the first successful tick
+    pub fn record_success(&mut self) {
+        self.consecutive_failures = 0;
+    }
+
+    /// Counts a failed tick and returns how long to back off, if the breaker is open
+    pub fn record_failure(&mut self) -> Option<Duration> {
+        self.consecutive_failures += 1;
+        (self.consecutive_failures >= self.trip_after).then_some(self.cooldown)
+    }
+
+    pub fn consecutive_failures(&self) -> u32 {
+        self.consecutive_failures
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn opens_after_repeated_failures_and_resets_on_success() {
+        let cooldown = Duration::from_secs(300);
+        let mut breaker = CircuitBreaker::new(3, cooldown);
+
+        assert_eq!(breaker.record_failure(), None);
+        assert_eq!(breaker.record_failure(), None);
+        assert_eq!(breaker.record_failure(), Some(cooldown));
+        assert_eq!(breaker.record_failure(), Some(cooldown));
+
+        breaker.record_success();
+        assert_eq!(breaker.record_failure(), None);
+    }
+}
@@ -0,0 +1,58 @@
+use tokio::time::Instant;
+
+/// Where the bot reads the time from, so intervals, cooldowns and staleness can be
+/// tested without waiting on the real clock
+pub trait Clock: Send + Sync {
+    /// Monotonic time, for measuring intervals and cooldowns
+    fn now(&self) -> Instant;
+
+    /// Wall-clock time in Unix seconds, as heartbeats record it
+    fn unix_seconds(&self) -> u64;
+}
+
+/// The real clock
+pub struct SystemClock;
+
+impl Clock for SystemClock {
+    fn now(&self) -> Instant {
+        Instant::now()
+    }
+
+    fn unix_seconds(&self) -> u64 {
+        chrono::Utc::now().timestamp().max(0) as u64
+    }
+}
+
+/// A clock that only moves when advanced. Tokio's timers follow it when the test
+/// runtime starts paused (`#[tokio::test(start_paused = true)]`).
+#[cfg(test)]
+pub struct MockClock {
+    start: Instant,
+    unix_start: u64,
+}
+
+#[cfg(test)]
+impl MockClock {
+    pub fn new(unix_start: u64) -> Self {
+        MockClock {
+            start: Instant::now(),
+            unix_start,
+        }
+    }
+
+    /// Moves time forward, firing any timers that come due on the way
+    pub async fn advance(&self, by: std::time::Duration) {
+        tokio::time::advance(by).await;
+    }
+}
+
+#[cfg(test)]
+impl Clock for MockClock {
+    fn now(&self) -> Instant {
+        Instant::now()
+    }
+
+    fn unix_seconds(&self) -> u64 {
+        self.unix_start + (Instant::now() - self.start).as_secs()
+    }
+}
@@ -0,0 +1,47 @@
+use crate::error::BotError;
+use crate::Opportunity;
+use ethers::providers::Middleware;
+use ethers::types::H256;
+use eyre::Result;
+use std::time::Duration;
+
+/// Opportunities held back until the block they were priced at has `depth` blocks on
+/// top of it, so one found in a block that is later orphaned is never reported
+pub struct Confirmations {
+    depth: u64,
+    pending: Vec<(H256, Opportunity)>,
+}
+
+impl Confirmations {
+    pub fn new(depth: u64) -> Self {
+        Confirmations {
+            depth,
+            pending: Vec::new(),
+        }
+    }
+
+    /// Holds an opportunity priced at the block with this hash, which must be its `block_number`
+    pub fn hold(&mut self, opportunity: Opportunity, block_hash: H256) {
+        self.pending.push((block_hash, opportunity));
+    }
+
+    /// Removes and returns the held opportunities whose block is confirmed deeply enough
+    /// as of block `latest`, with the hash their block had when they were found
+    pub fn matured(&mut self, latest: u64) -> Vec<(H256, Opportunity)> {
+        let depth = self.depth;
+        let (matured, pending) = std::mem::take(&mut self.pending)
+            .into_iter()
+            .partition(|(_, opportunity)| opportunity.block_number.is_some_and(|number| number + depth <= latest));
+        self.pending = pending;
+        matured
+    }
+}
+
+/// The hash of the canonical block at `number`, or `None` if the node doesn't have it
+pub async fn block_hash<M: Middleware + 'static>(client: &M, number: u64, timeout: Duration) -> Result<Option<H256>> {
+    let block = tokio::time::timeout(timeout, client.get_block(number))
+        .await
+        .map_err(|_| BotError::Timeout(timeout))?
+        .map_err(|e| BotError::Rpc(e.to_string()))?;
+    Ok(block.and_then(|block| block.hash))
+}
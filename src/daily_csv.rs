@@ -0,0 +1,136 @@
+use chrono::{DateTime, NaiveDate, Utc};
+use eyre::Result;
+use serde::Serialize;
+use std::fs::{File, OpenOptions};
+use std::io::ErrorKind;
+use std::path::{Path, PathBuf};
+
+/// Appends records to one CSV file per UTC day, `<prefix>-YYYY-MM-DD.csv` in `dir`.
+/// The day's file stays open between writes and a new one is started at midnight.
+pub struct DailyCsv {
+    dir: PathBuf,
+    prefix: &'static str,
+    open: Option<(NaiveDate, csv::Writer<File>)>,
+}
+
+impl DailyCsv {
+    pub fn new(dir: impl Into<PathBuf>, prefix: &'static str) -> Self {
+        DailyCsv {
+            dir: dir.into(),
+            prefix,
+            open: None,
+        }
+    }
+
+    /// The file records written at `now` go to
+    fn path_for(&self, now: DateTime<Utc>) -> PathBuf {
+        self.dir.join(format!("{}-{}.csv", self.prefix, now.format("%Y-%m-%d")))
+    }
+
+    pub fn write(&mut self, record: &impl Serialize, now: DateTime<Utc>) -> Result<()> {
+        let today = now.date_naive();
+        let writer = match &mut self.open {
+            Some((date, writer)) if *date == today => writer,
+            _ => {
+                std::fs::create_dir_all(&self.dir)?;
+                // Restarting mid-day keeps appending to the day's file
+                let writer = append_to(&self.path_for(now), record)?;
+                &mut self.open.insert((today, writer)).1
+            }
+        };
+        writer.serialize(record)?;
+        writer.flush()?;
+        Ok(())
+    }
+}
+
+/// Opens the CSV file at `path` to append rows like `record` to, starting it with a
+/// header if it's new. A file whose header has other columns, e.g. one written by an older
+/// version, is an error: appending would put the new rows under the wrong columns.
+pub fn append_to(path: &Path, record: &impl Serialize) -> Result<csv::Writer<File>> {
+    let expected = header_of(record)?;
+    let existing = match File::open(path) {
+        Ok(file) => csv::ReaderBuilder::new().has_headers(false).from_reader(file).records().next().transpose()?,
+        Err(e) if e.kind() == ErrorKind::NotFound => None,
+        Err(e) => return Err(e.into()),
+    };
+    if let Some(existing) = existing.as_ref().filter(|existing| **existing != expected) {
+        return Err(eyre::eyre!(
+            "{} has the columns {}, not {}; move it aside so a new file is started",
+            path.display(),
+            existing.iter().collect::<Vec<_>>().join(","),
+            expected.iter().collect::<Vec<_>>().join(",")
+        ));
+    }
+    let file = OpenOptions::new().create(true).append(true).open(path)?;
+    Ok(csv::WriterBuilder::new().has_headers(existing.is_none()).from_writer(file))
+}
+
+/// The header row the csv crate writes for `record`
+fn header_of(record: &impl Serialize) -> Result<csv::StringRecord> {
+    let mut writer = csv::Writer::from_writer(Vec::new());
+    writer.serialize(record)?;
+    let written = writer.into_inner().map_err(|e| eyre::eyre!("{}", e))?;
+    Ok(csv::Reader::from_reader(written.as_slice()).headers()?.clone())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+
+    #[derive(Serialize)]
+    struct Row {
+        value: u32,
+    }
+
+    #[derive(Serialize)]
+    struct NewRow {
+        value: u32,
+        tier: &'static str,
+    }
+
+    #[test]
+    fn rolls_over_at_utc_midnight() {
+        let dir = std::env::temp_dir().join(format!("daily-csv-test-{}", std::process::id()));
+        let mut csv = DailyCsv::new(&dir, "opportunities");
+        let before = Utc.with_ymd_and_hms(2024, 6, 1, 23, 59, 0).unwrap();
+        let after = Utc.with_ymd_and_hms(2024, 6, 2, 0, 1, 0).unwrap();
+
+        csv.write(&Row { value: 1 }, before).unwrap();
+        csv.write(&Row { value: 2 }, before).unwrap();
+        csv.write(&Row { value: 3 }, after).unwrap();
+
+        let first = std::fs::read_to_string(dir.join("opportunities-2024-06-01.csv")).unwrap();
+        let second = std::fs::read_to_string(dir.join("opportunities-2024-06-02.csv")).unwrap();
+        std::fs::remove_dir_all(&dir).unwrap();
+        assert_eq!(first, "value\n1\n2\n");
+        assert_eq!(second, "value\n3\n");
+    }
+
+    #[test]
+    fn refuses_to_append_under_an_older_header() {
+        let dir = std::env::temp_dir().join(format!("daily-csv-header-test-{}", std::process::id()));
+        let mut old = DailyCsv::new(&dir, "opportunities");
+        let now = Utc.with_ymd_and_hms(2024, 6, 1, 12, 0, 0).unwrap();
+        old.write(&Row { value: 1 }, now).unwrap();
+
+        // Restarted after an upgrade added a column
+        let mut new = DailyCsv::new(&dir, "opportunities");
+        let error = new.write(&NewRow { value: 2, tier: "strong" }, now).unwrap_err();
+        assert!(error.to_string().contains("has the columns value, not value,tier"), "{}", error);
+        let path = dir.join("opportunities-2024-06-01.csv");
+        assert_eq!(std::fs::read_to_string(&path).unwrap(), "value\n1\n");
+
+        // The same columns keep appending, and an empty file gets a header
+        old.write(&Row { value: 3 }, now).unwrap();
+        assert_eq!(std::fs::read_to_string(&path).unwrap(), "value\n1\n3\n");
+        std::fs::write(&path, "").unwrap();
+        let mut writer = append_to(&path, &NewRow { value: 5, tier: "info" }).unwrap();
+        writer.serialize(NewRow { value: 5, tier: "info" }).unwrap();
+        writer.flush().unwrap();
+        let contents = std::fs::read_to_string(&path).unwrap();
+        std::fs::remove_dir_all(&dir).unwrap();
+        assert_eq!(contents, "value,tier\n5,info\n");
+    }
+}
@@ -0,0 +1,179 @@
+use crate::Opportunity;
+use chrono::{DateTime, Utc};
+use eyre::Result;
+use rusqlite::{params, Connection};
+use std::sync::mpsc;
+use std::thread::JoinHandle;
+use tracing::error;
+
+const SCHEMA: &str = "
+    CREATE TABLE IF NOT EXISTS prices (
+        timestamp TEXT NOT NULL,
+        dex_name TEXT NOT NULL,
+        pair TEXT NOT NULL,
+        amount_in REAL NOT NULL,
+        amount_out REAL NOT NULL,
+        block_number INTEGER
+    );
+    CREATE TABLE IF NOT EXISTS opportunities (
+        timestamp TEXT NOT NULL,
+        pair TEXT NOT NULL,
+        buy_dex TEXT NOT NULL,
+        sell_dex TEXT NOT NULL,
+        buy_price REAL NOT NULL,
+        sell_price REAL NOT NULL,
+        gross_profit REAL NOT NULL,
+        fees REAL NOT NULL,
+        gas_cost REAL NOT NULL,
+        net_profit REAL NOT NULL,
+        profit_percent REAL NOT NULL,
+        block_number INTEGER,
+        id TEXT,
+        tier TEXT
+    );
+";
+
+/// Columns added after the first release, which older databases are missing, with their types
+const ADDED_COLUMNS: [(&str, &str, &str); 4] = [
+    ("prices", "block_number", "INTEGER"),
+    ("opportunities", "block_number", "INTEGER"),
+    ("opportunities", "id", "TEXT"),
+    ("opportunities", "tier", "TEXT"),
+];
+
+/// A price quote as fetched from one DEX. Every quote for a pair in one tick
+/// shares the same timestamp, which is how backtests regroup them.
+#[derive(Debug, Clone)]
+pub struct PriceRow {
+    pub timestamp: DateTime<Utc>,
+    pub block_number: Option<u64>,
+    pub dex_name: String,
+    pub pair: String,
+    pub amount_in: f64,
+    pub amount_out: f64,
+}
+
+enum Row {
+    Price(PriceRow),
+    Opportunity(Box<Opportunity>),
+}
+
+/// SQLite history of prices and opportunities. Rows are handed to a background
+/// writer thread so inserting never blocks the fetch path.
+pub struct Database {
+    sender: mpsc::Sender<Row>,
+    writer: JoinHandle<()>,
+}
+
+impl Database {
+    /// Opens (or creates) the database and its tables, then starts the writer thread
+    pub fn open(path: &str) -> Result<Self> {
+        let conn = Connection::open(path)?;
+        conn.execute_batch(SCHEMA)?;
+        for (table, column, column_type) in ADDED_COLUMNS {
+            add_missing_column(&conn, table, column, column_type)?;
+        }
+
+        let (sender, receiver) = mpsc::channel();
+        let writer = std::thread::spawn(move || write_rows(conn, receiver));
+        Ok(Database { sender, writer })
+    }
+
+    pub fn record_price(&self, row: PriceRow) {
+        // The writer only goes away if it panicked, which it has already logged
+        let _ = self.sender.send(Row::Price(row));
+    }
+
+    pub fn record_opportunity(&self, opportunity: &Opportunity) {
+        let _ = self.sender.send(Row::Opportunity(Box::new(opportunity.clone())));
+    }
+
+    /// Waits for every queued row to be written
+    pub fn close(self) {
+        drop(self.sender);
+        let _ = self.writer.join();
+    }
+}
+
+/// Adds a nullable column to a table created before the column existed
+fn add_missing_column(conn: &Connection, table: &str, column: &str, column_type: &str) -> Result<()> {
+    let mut stmt = conn.prepare(&format!("SELECT name FROM pragma_table_info('{}')", table))?;
+    let columns = stmt.query_map([], |row| row.get::<_, String>(0))?.collect::<rusqlite::Result<Vec<_>>>()?;
+    if !columns.iter().any(|name| name == column) {
+        conn.execute_batch(&format!("ALTER TABLE {} ADD COLUMN {} {}", table, column, column_type))?;
+    }
+    Ok(())
+}
+
+/// Writes rows until every sender is dropped, batching whatever has queued up
+/// since the last write into a single transaction
+fn write_rows(mut conn: Connection, receiver: mpsc::Receiver<Row>) {
+    while let Ok(first) = receiver.recv() {
+        let batch: Vec<Row> = std::iter::once(first).chain(receiver.try_iter()).collect();
+        if let Err(e) = insert_batch(&mut conn, &batch) {
+            error!("Error writing {} rows to the database: {}", batch.len(), e);
+        }
+    }
+}
+
+fn insert_batch(conn: &mut Connection, batch: &[Row]) -> rusqlite::Result<()> {
+    let tx = conn.transaction()?;
+    for row in batch {
+        match row {
+            Row::Price(price) => {
+                tx.execute(
+                    "INSERT INTO prices (timestamp, dex_name, pair, amount_in, amount_out, block_number)
+                     VALUES (?1, ?2, ?3, ?4, ?5, ?6)",
+                    params![
+                        price.timestamp.to_rfc3339(),
+                        price.dex_name,
+                        price.pair,
+                        price.amount_in,
+                        price.amount_out,
+                        price.block_number,
+                    ],
+                )?;
+            }
+            Row::Opportunity(opp) => {
+                tx.execute(
+                    "INSERT INTO opportunities (timestamp, pair, buy_dex, sell_dex, buy_price, sell_price,
+                        gross_profit, fees, gas_cost, net_profit, profit_percent, block_number, id, tier)
+                     VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12, ?13, ?14)",
+                    params![
+                        opp.timestamp.to_rfc3339(),
+                        opp.pair,
+                        opp.buy_dex,
+                        opp.sell_dex,
+                        opp.buy_price,
+                        opp.sell_price,
+                        opp.gross_profit,
+                        opp.fees,
+                        opp.gas_cost,
+                        opp.net_profit,
+                        opp.profit_percent,
+                        opp.block_number,
+                        opp.id,
+                        opp.tier.to_string(),
+                    ],
+                )?;
+            }
+        }
+    }
+    tx.commit()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn adds_block_number_to_an_older_database() {
+        let conn = Connection::open_in_memory().unwrap();
+        conn.execute_batch("CREATE TABLE prices (timestamp TEXT NOT NULL, amount_out REAL NOT NULL);").unwrap();
+
+        add_missing_column(&conn, "prices", "block_number", "INTEGER").unwrap();
+        // A second run finds the column already there
+        add_missing_column(&conn, "prices", "block_number", "INTEGER").unwrap();
+        conn.execute("INSERT INTO prices (timestamp, amount_out, block_number) VALUES ('t', 1.0, 42)", []).unwrap();
+    }
+}
@@ -0,0 +1,146 @@
+use crate::Opportunity;
+use std::collections::HashMap;
+use std::time::Duration;
+use tokio::time::Instant;
+
+/// Identifies "the same" opportunity across ticks. Profit is bucketed to 0.1% so
+/// small fluctuations in a persistent spread don't count as a new opportunity.
+/// Each trade size on a route is its own opportunity.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+struct Fingerprint {
+    pair: String,
+    buy_dex: String,
+    sell_dex: String,
+    /// The bits of `amount_in`, since floats aren't `Hash`
+    amount_in: u64,
+    profit_bucket: i64,
+}
+
+impl Fingerprint {
+    fn of(opportunity: &Opportunity) -> Self {
+        Fingerprint {
+            pair: opportunity.pair.clone(),
+            buy_dex: opportunity.buy_dex.clone(),
+            sell_dex: opportunity.sell_dex.clone(),
+            amount_in: opportunity.amount_in.to_bits(),
+            profit_bucket: (opportunity.profit_percent * 10.0).round() as i64,
+        }
+    }
+}
+
+struct Seen {
+    last_alerted: Instant,
+    last_tick: u64,
+}
+
+/// Suppresses repeat alerts for an opportunity that persists across ticks, until
+/// `cooldown` has passed or it disappears for at least one tick and comes back
+pub struct AlertFilter {
+    cooldown: Duration,
+    seen: HashMap<Fingerprint, Seen>,
+}
+
+impl AlertFilter {
+    pub fn new(cooldown: Duration) -> Self {
+        AlertFilter {
+            cooldown,
+            seen: HashMap::new(),
+        }
+    }
+
+    /// Records that `opportunity` was detected during `tick` and returns whether to alert on it
+    pub fn should_alert(&mut self, opportunity: &Opportunity, tick: u64, now: Instant) -> bool {
+        // Anything missing from the previous tick has disappeared, so it alerts again if it returns
+        self.seen.retain(|_, seen| seen.last_tick + 1 >= tick);
+
+        let fingerprint = Fingerprint::of(opportunity);
+        match self.seen.get_mut(&fingerprint) {
+            Some(seen) if now.duration_since(seen.last_alerted) < self.cooldown => {
+                seen.last_tick = tick;
+                false
+            }
+            _ => {
+                let seen = Seen {
+                    last_alerted: now,
+                    last_tick: tick,
+                };
+                self.seen.insert(fingerprint, seen);
+                true
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::Utc;
+
+    fn opportunity(profit_percent: f64) -> Opportunity {
+        Opportunity {
+            id: String::new(),
+            timestamp: Utc::now(),
+            block_number: None,
+            pair: "WETH/USDC".to_string(),
+            symbol_a: "WETH".to_string(),
+            symbol_b: "USDC".to_string(),
+            amount_in: 1.0,
+            buy_dex: "QuickSwap".to_string(),
+            sell_dex: "SushiSwap".to_string(),
+            buy_price: 1000.0,
+            sell_price: 1010.0,
+            round_trip_out: 1010.0,
+            gross_profit: 10.0,
+            fees: 0.0,
+            slippage: 0.0,
+            transfer_tax: 0.0,
+            gas_cost: 2.0,
+            gas_estimate: None,
+            net_profit: 8.0,
+            net_profit_in_currency: None,
+            profit_percent,
+            price_impact_pct: 0.0,
+            buy_deviation_pct: None,
+            sell_deviation_pct: None,
+            confirmations: None,
+            tier: crate::tier::Tier::Info,
+        }
+    }
+
+    #[test]
+    fn suppresses_a_persistent_opportunity_until_the_cooldown_passes() {
+        let start = Instant::now();
+        let at = |seconds| start + Duration::from_secs(seconds);
+        let mut alerts = AlertFilter::new(Duration::from_secs(60));
+
+        assert!(alerts.should_alert(&opportunity(0.8), 1, at(0)));
+        assert!(!alerts.should_alert(&opportunity(0.81), 2, at(30)));
+        // A different profit bucket counts as a new opportunity
+        assert!(alerts.should_alert(&opportunity(1.5), 2, at(30)));
+        assert!(alerts.should_alert(&opportunity(0.8), 3, at(60)));
+    }
+
+    #[test]
+    fn tells_trade_sizes_on_one_route_apart() {
+        let start = Instant::now();
+        let mut alerts = AlertFilter::new(Duration::from_secs(600));
+        let larger = Opportunity {
+            amount_in: 5.0,
+            ..opportunity(0.8)
+        };
+
+        assert!(alerts.should_alert(&opportunity(0.8), 1, start));
+        assert!(alerts.should_alert(&larger, 1, start));
+        assert!(!alerts.should_alert(&larger, 2, start + Duration::from_secs(10)));
+    }
+
+    #[test]
+    fn alerts_again_when_an_opportunity_reappears() {
+        let start = Instant::now();
+        let mut alerts = AlertFilter::new(Duration::from_secs(600));
+
+        assert!(alerts.should_alert(&opportunity(0.8), 1, start));
+        // Gone during tick 2, back in tick 3
+        assert!(alerts.should_alert(&opportunity(0.8), 3, start + Duration::from_secs(10)));
+    }
+}
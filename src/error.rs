@@ -0,0 +1,61 @@
+use ethers::prelude::{ContractError, Middleware};
+use ethers::utils::ConversionError;
+use std::time::Duration;
+use thiserror::Error;
+
+/// Errors raised while loading configuration or fetching prices
+#[derive(Debug, Error)]
+pub enum BotError {
+    /// The RPC node couldn't be reached or returned a transport-level error
+    #[error("RPC call failed: {0}")]
+    Rpc(String),
+
+    /// The call reached the node but reverted or returned undecodable data
+    #[error("contract call failed: {0}")]
+    Contract(String),
+
+    /// The call reverted with an `Error(string)` reason
+    #[error("reverted: {0}")]
+    Reverted(String),
+
+    /// `settings.toml` is missing, malformed, or doesn't match `Settings`
+    #[error("invalid configuration: {0}")]
+    Config(#[from] config::ConfigError),
+
+    /// The node didn't answer within `rpc_timeout_seconds`
+    #[error("RPC call timed out after {0:?}")]
+    Timeout(Duration),
+
+    /// The router answered, but without an output amount for the path
+    #[error("could not get amount out from DEX")]
+    EmptyAmountsOut,
+
+    /// An amount couldn't be converted between readable and raw token units
+    #[error("amount conversion failed: {0}")]
+    Conversion(#[from] ConversionError),
+}
+
+impl<M: Middleware> From<ContractError<M>> for BotError {
+    fn from(e: ContractError<M>) -> Self {
+        // Nodes report reverts as RPC errors, but retrying one won't help
+        if let Some(reason) = e.decode_revert::<String>() {
+            return BotError::Reverted(reason);
+        }
+        if e.as_revert().is_some() {
+            return BotError::Contract(e.to_string());
+        }
+        match e {
+            ContractError::MiddlewareError { .. } | ContractError::ProviderError { .. } => {
+                BotError::Rpc(e.to_string())
+            }
+            _ => BotError::Contract(e.to_string()),
+        }
+    }
+}
+
+impl BotError {
+    /// Whether retrying the same call later might succeed
+    pub fn is_transient(&self) -> bool {
+        matches!(self, BotError::Rpc(_))
+    }
+}
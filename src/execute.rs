@@ -0,0 +1,74 @@
+use crate::Opportunity;
+use async_trait::async_trait;
+use eyre::Result;
+use serde::{Deserialize, Serialize};
+use tracing::{error, info, warn};
+
+/// Whether detected opportunities are only simulated or handed to a live executor
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ExecutionMode {
+    #[default]
+    Simulate,
+    Live,
+}
+
+/// Acts on an opportunity once it has been detected and reported
+#[async_trait]
+pub trait Executor: Send + Sync {
+    /// Short name used in log messages
+    fn name(&self) -> &'static str;
+
+    async fn execute(&self, opportunity: &Opportunity) -> Result<()>;
+}
+
+/// Builds the executor for the configured mode
+pub fn for_mode(mode: ExecutionMode) -> Box<dyn Executor> {
+    match mode {
+        ExecutionMode::Simulate => Box::new(SimulatedExecutor),
+        ExecutionMode::Live => Box::new(LiveExecutor),
+    }
+}
+
+/// Executes an opportunity, logging rather than propagating failures so one bad
+/// trade never stops the bot
+pub async fn submit(executor: &dyn Executor, opportunity: &Opportunity) {
+    if let Err(e) = executor.execute(opportunity).await {
+        error!(pair = %opportunity.pair, "{} execution failed: {}", executor.name(), e);
+    }
+}
+
+/// Never trades; the opportunity has already been logged, so this only notes that
+pub struct SimulatedExecutor;
+
+#[async_trait]
+impl Executor for SimulatedExecutor {
+    fn name(&self) -> &'static str {
+        "Simulated"
+    }
+
+    async fn execute(&self, opportunity: &Opportunity) -> Result<()> {
+        info!(pair = %opportunity.pair, "Simulation only, no transaction sent.");
+        Ok(())
+    }
+}
+
+/// Placeholder for submitting real swaps. It doesn't sign or send anything yet.
+pub struct LiveExecutor;
+
+#[async_trait]
+impl Executor for LiveExecutor {
+    fn name(&self) -> &'static str {
+        "Live"
+    }
+
+    async fn execute(&self, opportunity: &Opportunity) -> Result<()> {
+        warn!(
+            pair = %opportunity.pair,
+            "Would submit tx: buy on {}, sell on {} (live execution isn't implemented yet)",
+            opportunity.buy_dex,
+            opportunity.sell_dex
+        );
+        Ok(())
+    }
+}
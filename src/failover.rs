@@ -0,0 +1,93 @@
+use tracing::{info, warn};
+
+/// Rotates between interchangeable RPC endpoints when the active one keeps failing
+#[derive(Debug)]
+pub struct Failover<T> {
+    endpoints: Vec<(String, T)>,
+    active: usize,
+    consecutive_failures: u32,
+    rotate_after: u32,
+}
+
+impl<T> Failover<T> {
+    /// `endpoints` pairs a display name with each endpoint and must not be empty
+    pub fn new(endpoints: Vec<(String, T)>, rotate_after: u32) -> Self {
+        assert!(!endpoints.is_empty(), "at least one RPC endpoint is required");
+        Failover {
+            endpoints,
+            active: 0,
+            consecutive_failures: 0,
+            rotate_after: rotate_after.max(1),
+        }
+    }
+
+    pub fn active(&self) -> &T {
+        &self.endpoints[self.active].1
+    }
+
+    pub fn active_name(&self) -> &str {
+        &self.endpoints[self.active].0
+    }
+
+    pub fn record_success(&mut self) {
+        self.consecutive_failures = 0;
+    }
+
+    /// Counts a failure against the active endpoint, rotating to the next one
+    /// once `rotate_after` failures have happened in a row
+    pub fn record_failure(&mut self) {
+        self.consecutive_failures += 1;
+        if self.consecutive_failures < self.rotate_after || self.endpoints.len() < 2 {
+            return;
+        }
+
+        let failed = self.active;
+        self.active = (self.active + 1) % self.endpoints.len();
+        self.consecutive_failures = 0;
+        warn!(
+            "RPC endpoint {} failed {} times in a row, rotating to {} ({}/{})",
+            self.endpoints[failed].0,
+            self.rotate_after,
+            self.active_name(),
+            self.active + 1,
+            self.endpoints.len(),
+        );
+    }
+
+    /// Logs the endpoints in rotation order
+    pub fn log_endpoints(&self) {
+        for (i, (name, _)) in self.endpoints.iter().enumerate() {
+            info!("RPC endpoint {}/{}: {}", i + 1, self.endpoints.len(), name);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn failover() -> Failover<u32> {
+        Failover::new(vec![("a".into(), 1), ("b".into(), 2)], 2)
+    }
+
+    #[test]
+    fn rotates_after_consecutive_failures() {
+        let mut failover = failover();
+        failover.record_failure();
+        assert_eq!(failover.active_name(), "a");
+        failover.record_failure();
+        assert_eq!(failover.active_name(), "b");
+        failover.record_failure();
+        failover.record_failure();
+        assert_eq!(failover.active_name(), "a");
+    }
+
+    #[test]
+    fn success_resets_the_failure_count() {
+        let mut failover = failover();
+        failover.record_failure();
+        failover.record_success();
+        failover.record_failure();
+        assert_eq!(*failover.active(), 1);
+    }
+}
@@ -0,0 +1,85 @@
+use std::collections::HashMap;
+use std::sync::Mutex;
+use std::time::Duration;
+use tokio::time::Instant;
+
+/// When each DEX last returned a price for each pair, so a DEX whose quotes keep failing
+/// can't silently drop out of the comparison. Pairs are tracked apart, since a DEX can
+/// keep quoting one pair while its pool for another stops answering.
+pub struct Freshness {
+    max_age: Duration,
+    /// Keyed by DEX name and pair label
+    last_fetched: Mutex<HashMap<(String, String), Instant>>,
+}
+
+impl Freshness {
+    /// Every DEX starts out fresh for every pair, so the first ticks after startup aren't skipped
+    pub fn new<'a>(
+        dex_names: impl IntoIterator<Item = &'a str>,
+        pair_labels: impl IntoIterator<Item = &'a str> + Clone,
+        max_age: Duration,
+        now: Instant,
+    ) -> Self {
+        let last_fetched = dex_names
+            .into_iter()
+            .flat_map(|dex| pair_labels.clone().into_iter().map(move |pair| ((dex.to_string(), pair.to_string()), now)))
+            .collect();
+        Freshness {
+            max_age,
+            last_fetched: Mutex::new(last_fetched),
+        }
+    }
+
+    pub fn record_fetch(&self, dex_name: &str, pair_label: &str, now: Instant) {
+        self.last_fetched.lock().unwrap().insert((dex_name.to_string(), pair_label.to_string()), now);
+    }
+
+    /// The DEXes that haven't produced a price for `pair_label` within the threshold, by name
+    pub fn stale(&self, pair_label: &str, now: Instant) -> Vec<String> {
+        let mut stale: Vec<String> = self
+            .last_fetched
+            .lock()
+            .unwrap()
+            .iter()
+            .filter(|((_, pair), &fetched)| pair == pair_label && now.duration_since(fetched) > self.max_age)
+            .map(|((dex, _), _)| dex.clone())
+            .collect();
+        stale.sort();
+        stale
+    }
+
+    pub fn max_age(&self) -> Duration {
+        self.max_age
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn flags_dexes_without_a_recent_price() {
+        let start = Instant::now();
+        let freshness = Freshness::new(["QuickSwap", "SushiSwap"], ["WETH/USDC"], Duration::from_secs(60), start);
+        assert!(freshness.stale("WETH/USDC", start + Duration::from_secs(60)).is_empty());
+
+        freshness.record_fetch("QuickSwap", "WETH/USDC", start + Duration::from_secs(50));
+        assert_eq!(freshness.stale("WETH/USDC", start + Duration::from_secs(90)), vec!["SushiSwap"]);
+    }
+
+    #[test]
+    fn tracks_each_pair_apart() {
+        let start = Instant::now();
+        let pairs = ["WETH/USDC", "WMATIC/USDC"];
+        let freshness = Freshness::new(["QuickSwap", "SushiSwap"], pairs, Duration::from_secs(60), start);
+        let later = start + Duration::from_secs(50);
+        freshness.record_fetch("QuickSwap", "WETH/USDC", later);
+        freshness.record_fetch("SushiSwap", "WETH/USDC", later);
+        freshness.record_fetch("QuickSwap", "WMATIC/USDC", later);
+
+        // SushiSwap answering for WETH/USDC doesn't vouch for its WMATIC/USDC quotes
+        let now = start + Duration::from_secs(90);
+        assert!(freshness.stale("WETH/USDC", now).is_empty());
+        assert_eq!(freshness.stale("WMATIC/USDC", now), vec!["SushiSwap"]);
+    }
+}
@@ -0,0 +1,218 @@
+use crate::error::BotError;
+use crate::ledger::{PaperLedger, SharedLedger};
+use crate::pause::Pause;
+use crate::Opportunity;
+use axum::extract::State;
+use axum::http::StatusCode;
+use axum::routing::{get, post};
+use axum::{Json, Router};
+use chrono::{DateTime, Utc};
+use eyre::Result;
+use serde::Serialize;
+use std::collections::VecDeque;
+use std::net::SocketAddr;
+use std::sync::{Arc, Mutex};
+use tracing::info;
+
+/// The most recently detected opportunities, oldest first
+pub type RecentOpportunities = Arc<Mutex<VecDeque<Opportunity>>>;
+
+/// How the main loop is doing, as reported by `GET /health`
+pub type SharedHealth = Arc<Mutex<Health>>;
+
+/// Adds an opportunity to the shared history, dropping the oldest beyond `max_len`
+pub fn record(recent: &RecentOpportunities, opportunity: Opportunity, max_len: usize) {
+    let mut recent = recent.lock().unwrap();
+    recent.push_back(opportunity);
+    while recent.len() > max_len {
+        recent.pop_front();
+    }
+}
+
+/// What kind of failure an error was, so monitors can tell a flaky RPC from a broken setup
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ErrorCategory {
+    /// The node was unreachable, erroring or too slow; usually passes on its own
+    Rpc,
+    /// A contract reverted or answered with nonsense, often a wrong address in the settings
+    Contract,
+    /// The settings themselves are invalid
+    Config,
+    Other,
+}
+
+impl ErrorCategory {
+    /// Categorizes by the first `BotError` in the error's chain
+    pub fn of(error: &eyre::Report) -> Self {
+        match error.chain().find_map(|cause| cause.downcast_ref::<BotError>()) {
+            Some(BotError::Rpc(_) | BotError::Timeout(_)) => ErrorCategory::Rpc,
+            Some(
+                BotError::Contract(_) | BotError::Reverted(_) | BotError::EmptyAmountsOut | BotError::Conversion(_),
+            ) => ErrorCategory::Contract,
+            Some(BotError::Config(_)) => ErrorCategory::Config,
+            None => ErrorCategory::Other,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct LastError {
+    pub at: DateTime<Utc>,
+    pub category: ErrorCategory,
+    pub message: String,
+}
+
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct Health {
+    /// When a tick last priced at least one pair
+    pub last_success: Option<DateTime<Utc>>,
+    /// Ticks in a row that priced nothing
+    pub consecutive_failures: u32,
+    pub last_error: Option<LastError>,
+    /// Whether the circuit breaker is backing off after repeated failures
+    pub breaker_open: bool,
+}
+
+impl Health {
+    pub fn record_error(&mut self, error: &eyre::Report) {
+        self.last_error = Some(LastError {
+            at: Utc::now(),
+            category: ErrorCategory::of(error),
+            message: format!("{:#}", error),
+        });
+    }
+
+    /// Records how a tick went; a tick that priced any pair counts as a success
+    pub fn record_tick(&mut self, succeeded: bool) {
+        if succeeded {
+            self.last_success = Some(Utc::now());
+            self.consecutive_failures = 0;
+        } else {
+            self.consecutive_failures += 1;
+        }
+    }
+
+    fn status(&self) -> (StatusCode, &'static str) {
+        match (self.breaker_open, self.consecutive_failures) {
+            (true, _) => (StatusCode::SERVICE_UNAVAILABLE, "breaker_open"),
+            (false, 0) => (StatusCode::OK, "ok"),
+            (false, _) => (StatusCode::OK, "failing"),
+        }
+    }
+}
+
+#[derive(Clone)]
+struct AppState {
+    recent: RecentOpportunities,
+    health: SharedHealth,
+    ledger: SharedLedger,
+    pause: Pause,
+}
+
+/// Serves `GET /opportunities`, `GET /health` and `GET /ledger`, and pauses and resumes
+/// scanning on `POST /pause` and `POST /resume`, until the listener fails
+pub async fn serve(
+    addr: SocketAddr,
+    recent: RecentOpportunities,
+    health: SharedHealth,
+    ledger: SharedLedger,
+    pause: Pause,
+) -> Result<()> {
+    let app = Router::new()
+        .route("/opportunities", get(opportunities))
+        .route("/health", get(health_report))
+        .route("/ledger", get(ledger_report))
+        .route("/pause", post(pause_scanning))
+        .route("/resume", post(resume_scanning))
+        .with_state(AppState { recent, health, ledger, pause });
+
+    let listener = tokio::net::TcpListener::bind(addr).await?;
+    info!("Serving opportunities on http://{}", addr);
+    axum::serve(listener, app).await?;
+    Ok(())
+}
+
+async fn opportunities(State(state): State<AppState>) -> Json<Vec<Opportunity>> {
+    Json(state.recent.lock().unwrap().iter().cloned().collect())
+}
+
+#[derive(Serialize)]
+struct HealthReport {
+    status: &'static str,
+    #[serde(flatten)]
+    health: Health,
+    last_opportunity: Option<DateTime<Utc>>,
+    paused: bool,
+}
+
+/// 503 while the circuit breaker is open, 200 otherwise, with the details as JSON
+async fn health_report(State(state): State<AppState>) -> (StatusCode, Json<HealthReport>) {
+    let health = state.health.lock().unwrap().clone();
+    let last_opportunity = state.recent.lock().unwrap().back().map(|opportunity| opportunity.timestamp);
+    let (code, status) = health.status();
+    let paused = state.pause.is_paused();
+    (code, Json(HealthReport { status, health, last_opportunity, paused }))
+}
+
+#[derive(Serialize)]
+struct PauseReport {
+    paused: bool,
+}
+
+async fn pause_scanning(State(state): State<AppState>) -> Json<PauseReport> {
+    state.pause.set(true, "POST /pause");
+    Json(PauseReport { paused: true })
+}
+
+async fn resume_scanning(State(state): State<AppState>) -> Json<PauseReport> {
+    state.pause.set(false, "POST /resume");
+    Json(PauseReport { paused: false })
+}
+
+#[derive(Serialize)]
+struct LedgerReport {
+    #[serde(flatten)]
+    ledger: PaperLedger,
+    win_rate_pct: Option<f64>,
+}
+
+async fn ledger_report(State(state): State<AppState>) -> Json<LedgerReport> {
+    let ledger = state.ledger.lock().unwrap().clone();
+    let win_rate_pct = ledger.win_rate_pct();
+    Json(LedgerReport { ledger, win_rate_pct })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn reports_unavailable_only_while_the_breaker_is_open() {
+        let mut health = Health::default();
+        assert_eq!(health.status(), (StatusCode::OK, "ok"));
+
+        health.record_tick(false);
+        health.record_error(&eyre::Report::new(BotError::Rpc("connection refused".to_string())));
+        assert_eq!(health.status(), (StatusCode::OK, "failing"));
+        assert_eq!(health.last_error.as_ref().unwrap().category, ErrorCategory::Rpc);
+
+        health.breaker_open = true;
+        assert_eq!(health.status().0, StatusCode::SERVICE_UNAVAILABLE);
+
+        health.breaker_open = false;
+        health.record_tick(true);
+        assert_eq!(health.status(), (StatusCode::OK, "ok"));
+        assert!(health.last_success.is_some());
+    }
+
+    #[test]
+    fn categorizes_by_the_underlying_bot_error() {
+        let wrapped = eyre::Report::new(BotError::Contract("reverted".to_string())).wrap_err("1 of 2 DEX quotes failed");
+        assert_eq!(ErrorCategory::of(&wrapped), ErrorCategory::Contract);
+        let mut health = Health::default();
+        health.record_error(&wrapped);
+        assert_eq!(health.last_error.unwrap().message, "1 of 2 DEX quotes failed: contract call failed: reverted");
+        assert_eq!(ErrorCategory::of(&eyre::eyre!("no fresh price")), ErrorCategory::Other);
+    }
+}
@@ -0,0 +1,89 @@
+use eyre::Result;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::path::Path;
+use std::sync::{Arc, Mutex};
+use tracing::info;
+
+/// The ledger shared between the main loop and `GET /ledger`
+pub type SharedLedger = Arc<Mutex<PaperLedger>>;
+
+/// What the simulated profits would total if every reported opportunity had been taken
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct PaperLedger {
+    pub trades: u64,
+    /// Trades whose simulated net profit was positive
+    pub wins: u64,
+    /// Cumulative simulated net profit, by the token it is in, since pairs can report
+    /// profits in different tokens
+    pub total_profit: BTreeMap<String, f64>,
+}
+
+impl PaperLedger {
+    /// Loads the ledger saved at `path`, or starts an empty one if there is none yet
+    pub fn load(path: &str) -> Result<Self> {
+        if !Path::new(path).exists() {
+            return Ok(PaperLedger::default());
+        }
+        Ok(serde_json::from_str(&std::fs::read_to_string(path)?)?)
+    }
+
+    /// Writes the ledger to `path`, through a temporary file so a crash mid-write
+    /// can't leave it truncated
+    pub fn save(&self, path: &str) -> Result<()> {
+        let temporary = format!("{}.tmp", path);
+        std::fs::write(&temporary, serde_json::to_string_pretty(self)?)?;
+        std::fs::rename(&temporary, path)?;
+        Ok(())
+    }
+
+    /// Takes a trade with this simulated net profit, in `currency`
+    pub fn record(&mut self, net_profit: f64, currency: &str) {
+        self.trades += 1;
+        if net_profit > 0.0 {
+            self.wins += 1;
+        }
+        *self.total_profit.entry(currency.to_string()).or_default() += net_profit;
+    }
+
+    /// The share of trades that were profitable, as a percentage
+    pub fn win_rate_pct(&self) -> Option<f64> {
+        (self.trades > 0).then(|| self.wins as f64 / self.trades as f64 * 100.0)
+    }
+
+    /// Logs the totals, with profits rounded to `decimals` places
+    pub fn log_summary(&self, decimals: usize) {
+        info!("  - Paper trades: {}", self.trades);
+        match self.win_rate_pct() {
+            Some(win_rate) => info!("  - Paper win rate: {:.1}%", win_rate),
+            None => info!("  - Paper win rate: n/a"),
+        }
+        for (currency, profit) in &self.total_profit {
+            info!("  - Paper PnL: {:.*} {}", decimals, profit, currency);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn totals_profits_per_currency_and_survives_a_restart() {
+        let mut ledger = PaperLedger::default();
+        assert_eq!(ledger.win_rate_pct(), None);
+        ledger.record(2.5, "USDC");
+        ledger.record(-0.5, "USDC");
+        ledger.record(0.001, "WETH");
+        assert_eq!(ledger.trades, 3);
+        assert_eq!(ledger.total_profit["USDC"], 2.0);
+        assert!((ledger.win_rate_pct().unwrap() - 200.0 / 3.0).abs() < 1e-9);
+
+        let path = std::env::temp_dir().join(format!("paper-ledger-{}.json", std::process::id()));
+        let path = path.to_str().unwrap();
+        assert_eq!(PaperLedger::load(path).unwrap(), PaperLedger::default());
+        ledger.save(path).unwrap();
+        assert_eq!(PaperLedger::load(path).unwrap(), ledger);
+        std::fs::remove_file(path).unwrap();
+    }
+}
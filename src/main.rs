@@ -1,10 +1,57 @@
 use ethers::prelude::*;
-use ethers::providers::{Provider, Http};
-use ethers::core::utils::parse_units;
+use ethers::providers::{Http, Provider, Ws};
+use ethers::core::utils::{format_units, parse_units};
 use eyre::Result;
+use futures::future::join_all;
+use futures::{Stream, StreamExt};
+use chrono::{DateTime, Utc};
+use tracing::{debug, error, info, warn};
+use tracing_subscriber::EnvFilter;
+use clap::Parser;
+use rand::Rng;
+use std::collections::HashMap;
+use std::net::{IpAddr, Ipv4Addr, SocketAddr};
+use std::num::NonZeroU32;
+use std::str::FromStr;
 use std::sync::Arc;
 use std::time::Duration;
-use serde::Deserialize;
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+
+mod backtest;
+mod benchmark;
+mod breaker;
+mod clock;
+mod confirm;
+mod daily_csv;
+mod db;
+mod dedupe;
+mod error;
+mod execute;
+mod failover;
+mod freshness;
+mod http;
+mod ledger;
+mod metrics;
+mod notify;
+mod output;
+mod pause;
+mod pool;
+mod rate_limit;
+mod reference;
+mod registry;
+mod reload;
+mod rpc_log;
+mod sink;
+mod source;
+mod tier;
+mod spread_window;
+mod stats;
+mod watchdog;
+use clock::Clock;
+use error::BotError;
+use rate_limit::RateLimited;
+use source::PriceSource;
 
 // Generate typesafe bindings to the Uniswap V2 Router ABI
 abigen!(
@@ -13,120 +60,4644 @@ abigen!(
     event_derives(serde::Deserialize, serde::Serialize)
 );
 
+// Uniswap V3 Quoter bindings, for DEXes that don't implement getAmountsOut
+abigen!(IQuoterV2, "./src/abi/IQuoterV2.json");
+
+// Uniswap V2 factory and pool bindings, for reading pool reserves
+abigen!(IUniswapV2Factory, "./src/abi/IUniswapV2Factory.json");
+abigen!(IUniswapV2Pair, "./src/abi/IUniswapV2Pair.json");
+
+// Minimal ERC20 bindings, used to read token metadata at startup
+abigen!(IERC20, "./src/abi/IERC20.json");
+
+/// The contract a DEX is quoted through, depending on its protocol version
+enum Quoter<M> {
+    V2(IUniswapV2Router02<M>),
+    V3 { quoter: IQuoterV2<M>, fee_tier: u32 },
+}
+
+impl<M: Middleware> Quoter<M> {
+    fn new(dex: &Dex, client: Arc<M>) -> Self {
+        match dex.kind {
+            DexKind::V2 => Quoter::V2(IUniswapV2Router02::new(dex.router_address, client)),
+            DexKind::V3 => Quoter::V3 {
+                quoter: IQuoterV2::new(dex.router_address, client),
+                fee_tier: dex.fee_tier.expect("validated: v3 DEXes have a fee tier"),
+            },
+        }
+    }
+}
+
+#[async_trait::async_trait]
+impl<M: Middleware + 'static> PriceSource for Quoter<M> {
+    async fn amount_out(&self, amount_in: U256, path: &[Address], block: Option<BlockId>) -> Result<U256, BotError> {
+        match self {
+            Quoter::V2(router) => {
+                final_amount(&at_block(router.get_amounts_out(amount_in, path.to_vec()), block).call().await?)
+            }
+            Quoter::V3 { quoter, fee_tier } => match *path {
+                [token_in, token_out] => {
+                    let params = QuoteExactInputSingleParams {
+                        token_in,
+                        token_out,
+                        amount_in,
+                        fee: *fee_tier,
+                        sqrt_price_limit_x96: U256::zero(),
+                    };
+                    Ok(at_block(quoter.quote_exact_input_single(params), block).call().await?.0)
+                }
+                _ => Ok(at_block(quoter.quote_exact_input(v3_path(path, *fee_tier), amount_in), block).call().await?.0),
+            },
+        }
+    }
+}
+
+/// Makes `call` read the state at `block` instead of the latest block
+fn at_block<M: Middleware, D: abi::Detokenize>(call: ContractCall<M, D>, block: Option<BlockId>) -> ContractCall<M, D> {
+    match block {
+        Some(block) => call.block(block),
+        None => call,
+    }
+}
+
+/// The crate version with the commit and ethers version it was built from, set by build.rs
+const VERSION: &str = concat!(
+    env!("CARGO_PKG_VERSION"),
+    " (commit ",
+    env!("GIT_HASH"),
+    ", ethers ",
+    env!("ETHERS_VERSION"),
+    ")"
+);
+
+// Command-line options. With none, the bot runs its live detection loop.
+/// Detects arbitrage opportunities between DEXes on Polygon
+#[derive(Debug, Parser)]
+#[command(version = VERSION)]
+struct Cli {
+    /// Settings file to load; the extension may be omitted [default: the first found of
+    /// ./config/settings, $XDG_CONFIG_HOME/arb-bot/settings and settings next to the
+    /// executable, or none if ARB_* variables cover it]
+    #[arg(long)]
+    config: Option<String>,
+
+    /// Run a single check and exit instead of looping
+    #[arg(long)]
+    once: bool,
+
+    /// Seconds between checks, overriding `check_interval_seconds`
+    #[arg(long)]
+    interval: Option<u64>,
+
+    /// Stop after this many checks, overriding `max_iterations` (0 runs indefinitely)
+    #[arg(long)]
+    max_iterations: Option<u64>,
+
+    /// Stop after running this many seconds, overriding `max_runtime_seconds` (0 runs indefinitely)
+    #[arg(long)]
+    max_runtime: Option<u64>,
+
+    /// Run a single check against the state at this past block, to verify an earlier
+    /// opportunity (needs an archive node for blocks the node has pruned)
+    #[arg(long)]
+    at_block: Option<u64>,
+
+    /// Print every pair's DEX prices and spread to stdout each check, as CSV lines for
+    /// plotting, with the logs moved to stderr (same as `spread_stream = true`)
+    #[arg(long)]
+    spread_stream: bool,
+
+    /// Validate the settings, print them with overrides applied, and exit
+    #[arg(long)]
+    config_check: bool,
+
+    /// Replay the price history in `database_path` instead of running live
+    #[arg(long)]
+    backtest: bool,
+
+    /// Check back to back for this many seconds, ignoring the interval, then report
+    /// checks per second and quote latency, to pick a realistic `check_interval_seconds`
+    #[arg(
+        long,
+        value_name = "SECONDS",
+        value_parser = clap::value_parser!(u64).range(1..),
+        conflicts_with_all = ["once", "at_block", "backtest", "config_check"]
+    )]
+    benchmark: Option<u64>,
+
+    /// Append every raw `getAmountsOut` call (amount in, path, amounts out, block) to this
+    /// JSONL file, to replay and diff later (same as `record_rpc`)
+    #[arg(long, value_name = "PATH")]
+    record_rpc: Option<String>,
+
+    /// Profit threshold to evaluate in backtest mode; repeat to compare several
+    /// (defaults to `minimum_profit_threshold`)
+    #[arg(long = "threshold", requires = "backtest")]
+    thresholds: Vec<f64>,
+}
+
 // Configuration structs to hold settings from settings.toml
-#[derive(Debug, Deserialize)]
-struct Tokens {
-    weth: Address,
-    usdc: Address,
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+struct Pair {
+    label: String,
+    token_a: Address,
+    token_b: Address,
     decimals_a: u32,
     decimals_b: u32,
+    /// Token symbols for log output, read from the token contracts at startup if not set
+    symbol_a: Option<String>,
+    symbol_b: Option<String>,
+    /// Tokens to route through between Token A and Token B, in order
+    #[serde(default)]
+    intermediate_tokens: Vec<Address>,
+    /// Which token profits are reported and thresholded in
+    #[serde(default)]
+    profit_in: PairToken,
+    /// Quote Token B -> Token A instead, with `amount_in` in Token B
+    #[serde(default)]
+    invert: bool,
+    /// Overrides the global `minimum_profit_threshold` for this pair
+    minimum_profit_threshold: Option<f64>,
+}
+
+/// One side of a pair
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+enum PairToken {
+    A,
+    #[default]
+    B,
+}
+
+impl Pair {
+    fn symbol_a(&self) -> &str {
+        self.symbol_a.as_deref().unwrap_or("Token A")
+    }
+
+    fn symbol_b(&self) -> &str {
+        self.symbol_b.as_deref().unwrap_or("Token B")
+    }
+
+    /// The pair in the direction it is quoted. An inverted pair has its tokens swapped,
+    /// so everything downstream prices Token B -> Token A without special cases, while
+    /// `profit_in` still picks the same token it did in the settings.
+    fn oriented(self) -> Pair {
+        if !self.invert {
+            return self;
+        }
+        let mut intermediate_tokens = self.intermediate_tokens;
+        intermediate_tokens.reverse();
+        Pair {
+            token_a: self.token_b,
+            token_b: self.token_a,
+            decimals_a: self.decimals_b,
+            decimals_b: self.decimals_a,
+            symbol_a: self.symbol_b,
+            symbol_b: self.symbol_a,
+            intermediate_tokens,
+            profit_in: match self.profit_in {
+                PairToken::A => PairToken::B,
+                PairToken::B => PairToken::A,
+            },
+            invert: false,
+            ..self
+        }
+    }
+
+    /// The swap path from Token A to Token B, or back again when `reversed`
+    fn path(&self, reversed: bool) -> Vec<Address> {
+        let mut path = Vec::with_capacity(self.intermediate_tokens.len() + 2);
+        path.push(self.token_a);
+        path.extend(&self.intermediate_tokens);
+        path.push(self.token_b);
+        if reversed {
+            path.reverse();
+        }
+        path
+    }
+}
+
+/// A token cycle to check for triangular arbitrage on each DEX, e.g. WETH -> USDC -> WMATIC -> WETH
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+struct Cycle {
+    label: String,
+    /// The tokens to trade through in order; the path returns to the first one at the end
+    tokens: Vec<Address>,
+    /// Decimals of the first token, which `amount_in` and profits are denominated in
+    decimals: u32,
+    /// Symbol of the first token, read from its contract at startup if not set
+    symbol: Option<String>,
+}
+
+impl Cycle {
+    fn symbol(&self) -> &str {
+        self.symbol.as_deref().unwrap_or("Token")
+    }
+
+    /// The swap path around the cycle, starting and ending at the first token
+    fn path(&self) -> Vec<Address> {
+        let mut path = self.tokens.clone();
+        path.extend(self.tokens.first());
+        path
+    }
+}
+
+/// Which quoting interface a DEX implements
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+enum DexKind {
+    /// A Uniswap V2 style router with getAmountsOut
+    #[default]
+    V2,
+    /// A Uniswap V3 style QuoterV2 with quoteExactInputSingle
+    V3,
 }
 
-#[derive(Debug, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 struct Dex {
     name: String,
+    #[serde(default)]
+    kind: DexKind,
+    /// The V2 router, or the quoter for V3 DEXes
     router_address: Address,
+    /// The V3 pool fee tier to quote, in hundredths of a basis point (e.g. 500 = 0.05%)
+    fee_tier: Option<u32>,
+    /// Extra fee charged per swap leg, on top of what the router quote already includes
+    #[serde(default)]
+    fee_bps: u32,
+    /// Disabled DEXes stay in the config but are never quoted
+    #[serde(default = "default_enabled")]
+    enabled: bool,
 }
 
-#[derive(Debug, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 struct Settings {
     check_interval_seconds: u64,
+    /// Randomize each interval by up to this many seconds either way
+    interval_jitter_seconds: Option<f64>,
+    #[serde(default)]
+    run_once: bool,
+    /// Stop after this many ticks; unset or 0 runs indefinitely
+    max_iterations: Option<u64>,
+    /// Stop after running this long; unset or 0 runs indefinitely
+    max_runtime_seconds: Option<u64>,
+    /// Quote at this past block instead of the latest one; implies `run_once`
+    at_block: Option<u64>,
+    /// JSONL file every raw V2 `getAmountsOut` call and its result is appended to, for debugging
+    record_rpc: Option<String>,
+    /// Ticks to only price and log for after startup, before reporting opportunities
+    #[serde(default)]
+    warmup_ticks: u64,
+    /// Hold opportunities back until their block has this many blocks on top, dropping
+    /// those whose block was reorged out
+    confirmations: Option<u64>,
+    /// Quote this many blocks behind the latest, to avoid trusting very fresh state
+    #[serde(default)]
+    price_confirmations: u64,
     minimum_profit_threshold: f64,
-    amount_in: f64,
+    minimum_profit_percent: Option<f64>,
+    /// The same as `minimum_profit_percent` in basis points of the trade's value, e.g. 10 = 0.1%
+    minimum_profit_bps: Option<u32>,
+    /// Token A to trade; set this or `amount_in_usd`
+    amount_in: Option<AmountIn>,
+    /// Trade this many dollars of Token A instead, converted each tick at its USDC price
+    amount_in_usd: Option<f64>,
+    /// Trade sizes to try instead of just `amount_in`
+    amount_sweep: Option<AmountSweep>,
     simulated_gas_cost_usdc: f64,
-    tokens: Tokens,
+    #[serde(default = "default_gas_units")]
+    gas_units: u64,
+    /// Multiply the priority fee by this while the latest block is `congestion_threshold` full
+    congestion_factor: Option<f64>,
+    #[serde(default = "default_congestion_threshold")]
+    congestion_threshold: f64,
+    /// Don't report opportunities while the gas price is above this, as none would pay off
+    max_gas_price_gwei: Option<f64>,
+    #[serde(default = "default_max_retries")]
+    max_retries: u32,
+    #[serde(default = "default_retry_base_ms")]
+    retry_base_ms: u64,
+    #[serde(default = "default_rpc_timeout_seconds")]
+    rpc_timeout_seconds: u64,
+    /// How many pairs are scanned at once each tick
+    #[serde(default = "default_max_concurrency")]
+    max_concurrency: usize,
+    /// Caps RPC requests across all concurrent fetches; excess requests wait their turn
+    max_rpc_requests_per_second: Option<u32>,
+    /// HTTP connections opened to each RPC endpoint, which requests take turns on
+    #[serde(default = "default_provider_pool_size")]
+    provider_pool_size: usize,
+    /// Only report opportunities whose spread, averaged over this many checks, also clears the thresholds
+    spread_window: Option<usize>,
+    /// Opportunities whose quotes move the price by more than this are suppressed
+    max_price_impact_pct: Option<f64>,
+    /// Pairs are skipped when a V2 pool they'd trade through holds less Token B than this
+    /// is worth, in USDC at Token B's live price
+    min_liquidity_usdc: Option<f64>,
+    /// Pairs are skipped when a V2 pool they'd trade through hasn't traded for this long
+    max_pool_idle_seconds: Option<u64>,
+    /// Fee-on-transfer tokens and their tax, which quotes don't reflect, deducted from profits
+    #[serde(default)]
+    token_taxes: Vec<TokenTax>,
+    /// Pairs and cycles through a token taxed more than this are dropped at startup
+    max_tax_bps: Option<u32>,
+    /// Whether configured decimals that disagree with a token's contract only log an error
+    #[serde(default)]
+    on_decimals_mismatch: DecimalsMismatch,
+    /// Expected slippage on the sell leg, deducted from its output before computing net profit
+    #[serde(default)]
+    slippage_bps: u32,
+    /// Skip detection while any DEX hasn't returned a price for this long
+    staleness_threshold_seconds: Option<u64>,
+    #[serde(default)]
+    output_format: output::OutputFormat,
+    /// Print `timestamp,pair,<each DEX's price>,spread_pct` to stdout for every pair each tick
+    #[serde(default)]
+    spread_stream: bool,
+    /// Where prices and opportunities are recorded; defaults to every sink whose path is set
+    sinks: Option<Vec<sink::SinkKind>>,
+    output_csv: Option<String>,
+    /// Confirm opportunities by running both swaps with eth_call from `simulate_swap_from`
+    #[serde(default)]
+    simulate_swap: bool,
+    /// An account holding, and having approved the routers to spend, each pair's tokens
+    simulate_swap_from: Option<Address>,
+    /// Write opportunities to one dated CSV file per UTC day here, instead of `output_csv`
+    output_dir: Option<String>,
+    #[serde(default)]
+    rpc_urls: Vec<String>,
+    #[serde(default = "default_failover_after_failures")]
+    failover_after_failures: u32,
+    /// Back off for `breaker_cooldown_seconds` after this many failed ticks in a row
+    max_consecutive_failures: Option<u32>,
+    #[serde(default = "default_breaker_cooldown_seconds")]
+    breaker_cooldown_seconds: u64,
+    /// Log an error when no check has completed for this long
+    watchdog_timeout_seconds: Option<u64>,
+    /// Exit with status 1 when the watchdog fires, so a supervisor restarts the bot
+    #[serde(default)]
+    watchdog_exit: bool,
+    database_path: Option<String>,
+    /// Keep the paper trading ledger in this JSON file, so restarts don't reset it
+    paper_ledger_path: Option<String>,
+    http_port: Option<u16>,
+    metrics_port: Option<u16>,
+    /// The interface the HTTP and metrics servers listen on. Loopback by default, since
+    /// neither requires authentication and `POST /pause` stops scanning.
+    #[serde(default = "default_bind_address")]
+    bind_address: IpAddr,
+    discord_webhook_url: Option<String>,
+    /// Alert channels to send opportunities to, besides those enabled by environment variables
+    #[serde(default)]
+    notifiers: Vec<notify::NotifierEntry>,
+    /// Net profits that make an opportunity `notable` or `strong` rather than `info`
+    tiers: Option<tier::Tiers>,
+    /// Don't repeat an alert for the same opportunity within this many seconds
+    alert_cooldown_seconds: Option<u64>,
+    #[serde(default)]
+    execution_mode: execute::ExecutionMode,
+    /// Log a summary of the run every this many ticks
+    summary_every_n_ticks: Option<u64>,
+    /// Include this many of the widest spreads that didn't clear the thresholds in the summary
+    top_near_misses: Option<usize>,
+    /// Decimal places for prices and profits in logs and alerts; the CSV, JSON and
+    /// database outputs always keep full precision
+    #[serde(default = "default_display_decimals")]
+    display_decimals: usize,
+    #[serde(default = "default_max_recent_opportunities")]
+    max_recent_opportunities: usize,
+    wmatic: Address,
+    /// Log `wmatic` under this native token's symbol, e.g. "MATIC", since it is always worth
+    /// one of it. Routes still go through WMATIC.
+    native_symbol: Option<String>,
+    #[serde(default = "default_usdc")]
+    usdc: Address,
+    /// MATIC price to assume when it can't be quoted
+    fallback_matic_price_usdc: Option<f64>,
+    /// Converts pair profits into this token; `minimum_profit_threshold` is then in it too
+    profit_currency: Option<ProfitCurrency>,
+    /// Compare each DEX's price against an off-chain reference
+    reference_price_source: Option<reference::ReferencePriceSource>,
+    pairs: Vec<Pair>,
+    /// Also check `cycles` for triangular arbitrage within each DEX
+    #[serde(default)]
+    triangular: bool,
+    #[serde(default)]
+    cycles: Vec<Cycle>,
     dexes: Vec<Dex>,
 }
 
+/// The token to report and threshold pair profits in, instead of each pair's Token B
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+struct ProfitCurrency {
+    label: String,
+    token: Address,
+    /// Only used if the token's decimals can't be read at startup
+    decimals: u32,
+}
+
+/// What to do at startup when a token's configured decimals disagree with its contract's
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+enum DecimalsMismatch {
+    /// Log an error and carry on with the on-chain decimals
+    #[default]
+    Warn,
+    /// Refuse to start
+    Abort,
+}
+
+/// The Token A amount to trade, or several to check independently of each other
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(untagged)]
+enum AmountIn {
+    Single(f64),
+    Each(Vec<f64>),
+}
+
+impl AmountIn {
+    fn amounts(&self) -> Vec<f64> {
+        match self {
+            AmountIn::Single(amount) => vec![*amount],
+            AmountIn::Each(amounts) => amounts.clone(),
+        }
+    }
+}
+
+/// A token that takes `tax_bps` of every transfer
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+struct TokenTax {
+    token: Address,
+    tax_bps: u32,
+}
+
+/// Evenly spaced trade sizes from `min` to `max`, both included
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+struct AmountSweep {
+    min: f64,
+    max: f64,
+    steps: u32,
+}
+
+impl Settings {
+    /// The Token A amounts to simulate for each pair every tick
+    fn trade_sizes(&self) -> Vec<f64> {
+        match self.amount_sweep {
+            Some(AmountSweep { min, steps: 1, .. }) => vec![min],
+            Some(AmountSweep { min, max, steps }) => (0..steps)
+                .map(|i| min + (max - min) * f64::from(i) / f64::from(steps - 1))
+                .collect(),
+            None => self.amounts_in(),
+        }
+    }
+
+    /// The configured `amount_in` values, if trade sizes aren't given in USD
+    fn amounts_in(&self) -> Vec<f64> {
+        self.amount_in.as_ref().map_or_else(Vec::new, AmountIn::amounts)
+    }
+
+    /// Whether every trade size is reported on its own, rather than only the most profitable
+    fn reports_each_size(&self) -> bool {
+        self.amount_sweep.is_none() && matches!(self.amount_in, Some(AmountIn::Each(_)))
+    }
+
+    /// The transfer tax on `token`, in bps
+    fn tax_bps(&self, token: Address) -> u32 {
+        self.token_taxes.iter().filter(|tax| tax.token == token).map(|tax| tax.tax_bps).sum()
+    }
+
+    /// The transfer taxes paid along swap paths, in bps of the amount traded: every hop
+    /// moves its input token into a pool and the output token out of one
+    fn route_tax_bps<'a>(&self, paths: impl IntoIterator<Item = &'a Vec<Address>>) -> u32 {
+        paths.into_iter().flatten().map(|&token| self.tax_bps(token)).sum()
+    }
+
+    /// The tier of an opportunity, from its net profit in the token it was thresholded in.
+    /// Everything is `info` without `tiers`.
+    fn tier_of(&self, opportunity: &Opportunity) -> tier::Tier {
+        let profit = opportunity.net_profit_in_currency.unwrap_or(opportunity.net_profit);
+        self.tiers.map_or(tier::Tier::Info, |tiers| tiers.classify(profit))
+    }
+
+    fn thresholds(&self) -> Thresholds {
+        Thresholds {
+            minimum_profit: self.minimum_profit_threshold,
+            minimum_profit_percent: self
+                .minimum_profit_percent
+                .or(self.minimum_profit_bps.map(|minimum| f64::from(minimum) / 100.0)),
+        }
+    }
+
+    /// The thresholds for `pair`, with its own minimum profit if it sets one
+    fn thresholds_for(&self, pair: &Pair) -> Thresholds {
+        Thresholds {
+            minimum_profit: pair.minimum_profit_threshold.unwrap_or(self.minimum_profit_threshold),
+            ..self.thresholds()
+        }
+    }
+
+    /// The DEXes to quote, in config order
+    fn enabled_dexes(&self) -> Vec<Dex> {
+        self.dexes.iter().filter(|dex| dex.enabled).cloned().collect()
+    }
+
+    fn pacing(&self) -> Pacing {
+        Pacing {
+            interval: Duration::from_secs(self.check_interval_seconds),
+            jitter: Duration::from_secs_f64(self.interval_jitter_seconds.unwrap_or(0.0)),
+        }
+    }
+
+    /// The block to quote at given the latest one: `at_block`, or `price_confirmations`
+    /// blocks behind the latest. `None` quotes the latest block.
+    fn price_block(&self, latest: Option<u64>) -> Option<u64> {
+        match (self.at_block, latest) {
+            (Some(number), _) => Some(number),
+            (None, Some(latest)) if self.price_confirmations > 0 => {
+                Some(latest.saturating_sub(self.price_confirmations))
+            }
+            _ => None,
+        }
+    }
+
+    fn retry_policy(&self) -> RetryPolicy {
+        RetryPolicy {
+            max_retries: self.max_retries,
+            base_delay: Duration::from_millis(self.retry_base_ms),
+            timeout: Duration::from_secs(self.rpc_timeout_seconds),
+        }
+    }
+}
+
+/// How long to wait between polling ticks
+#[derive(Debug, Clone, Copy)]
+struct Pacing {
+    interval: Duration,
+    /// Each interval is shifted randomly by up to this much either way
+    jitter: Duration,
+}
+
+/// Profit an opportunity must exceed to be reported. Every configured threshold must pass.
+#[derive(Debug, Clone, Copy)]
+struct Thresholds {
+    /// Absolute net profit, in Token B
+    minimum_profit: f64,
+    /// Net profit relative to the Token B value of `amount_in`
+    minimum_profit_percent: Option<f64>,
+}
+
+impl Thresholds {
+    fn cleared_by(&self, net_profit: f64, profit_percent: f64) -> bool {
+        net_profit > self.minimum_profit
+            && self.minimum_profit_percent.is_none_or(|minimum| profit_percent > minimum)
+    }
+
+    /// How much more net profit a trade spending `buy_price` would have needed to clear
+    /// both thresholds
+    fn shortfall(&self, net_profit: f64, buy_price: f64) -> f64 {
+        let minimum_percent = self.minimum_profit_percent.map_or(f64::MIN, |percent| percent / 100.0 * buy_price);
+        self.minimum_profit.max(minimum_percent) - net_profit
+    }
+}
+
+/// Costs deducted from an opportunity's gross profit besides the DEX fees
+#[derive(Debug, Clone, Copy)]
+struct Costs {
+    /// Gas for the whole trade, in the token profits are measured in
+    gas: f64,
+    /// How `gas` was worked out, or `None` when it is `simulated_gas_cost_usdc`
+    gas_estimate: Option<GasEstimate>,
+    slippage_bps: u32,
+    /// Transfer taxes along the whole route, see `Settings::route_tax_bps`
+    tax_bps: u32,
+}
+
+impl Costs {
+    /// Costs from a gas estimate, falling back to `simulated_gas_cost_usdc` if it failed
+    fn new(gas_estimate: Option<GasEstimate>, settings: &Settings) -> Self {
+        Costs {
+            gas: gas_estimate.map_or(settings.simulated_gas_cost_usdc, |estimate| estimate.cost),
+            gas_estimate,
+            slippage_bps: settings.slippage_bps,
+            tax_bps: 0,
+        }
+    }
+
+    /// The same costs, plus transfer taxes of `tax_bps` of the amount traded
+    fn taxed(self, tax_bps: u32) -> Self {
+        Costs { tax_bps, ..self }
+    }
+}
+
+/// A live gas estimate: `units` * `price_gwei` = `cost_matic`, worth `cost` in the profit token
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+struct GasEstimate {
+    units: u64,
+    /// The EIP-1559 max fee per gas, or the legacy gas price
+    price_gwei: f64,
+    /// The EIP-1559 components of `price_gwei`, when the node supports fee history
+    fees: Option<Eip1559Fees>,
+    /// The latest block's gasUsed / gasLimit, if fetched, and the priority fee multiplier it led to
+    utilization: Option<f64>,
+    priority_fee_multiplier: f64,
+    cost_matic: f64,
+    cost: f64,
+}
+
+/// How an EIP-1559 max fee splits into the base fee allowance and the priority tip
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+struct Eip1559Fees {
+    base_fee_gwei: f64,
+    priority_fee_gwei: f64,
+}
+
+/// The node's fee data, as fetched once per tick
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum GasPrice {
+    Eip1559 { max_fee: U256, priority_fee: U256 },
+    /// From nodes without fee history
+    Legacy(U256),
+}
+
+/// Network conditions fetched once per tick and shared by every gas estimate
+#[derive(Debug, Clone, Copy, Default)]
+struct Network {
+    matic_price_usdc: Option<f64>,
+    /// The latest block's gasUsed / gasLimit, when congestion pricing is enabled
+    utilization: Option<f64>,
+    /// Unset if it couldn't be fetched, in which case gas costs fall back to `simulated_gas_cost_usdc`
+    gas_price: Option<GasPrice>,
+}
+
+impl Network {
+    /// How much to scale the priority fee by for the current congestion
+    fn priority_fee_multiplier(&self, settings: &Settings) -> f64 {
+        match (settings.congestion_factor, self.utilization) {
+            (Some(factor), Some(utilization)) if utilization >= settings.congestion_threshold => factor,
+            _ => 1.0,
+        }
+    }
+}
+
+/// How often and how patiently RPC calls are retried on transient failures
+#[derive(Debug, Clone, Copy)]
+struct RetryPolicy {
+    max_retries: u32,
+    base_delay: Duration,
+    /// Upper bound on a single call, so an unresponsive node can't stall the loop
+    timeout: Duration,
+}
+
+/// A simulated round trip whose net profit clears the threshold.
+/// Prices and profits are in the pair's Token B.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+struct Opportunity {
+    /// Identifies the opportunity across every output, see `Opportunity::stable_id`
+    id: String,
+    timestamp: DateTime<Utc>,
+    /// The block the tick was priced at, if the node reported one
+    block_number: Option<u64>,
+    pair: String,
+    /// The token `amount_in` is in, and the one prices and profits are in
+    symbol_a: String,
+    symbol_b: String,
+    /// The trade size in Token A, the most profitable one when sweeping sizes
+    amount_in: f64,
+    buy_dex: String,
+    sell_dex: String,
+    buy_price: f64,
+    sell_price: f64,
+    round_trip_out: f64,
+    gross_profit: f64,
+    fees: f64,
+    slippage: f64,
+    /// What fee-on-transfer tokens along the route would take, see `token_taxes`
+    transfer_tax: f64,
+    gas_cost: f64,
+    /// The breakdown of `gas_cost`, unless it was simulated
+    gas_estimate: Option<GasEstimate>,
+    net_profit: f64,
+    /// `net_profit` converted into the configured `profit_currency`, or into Token A
+    /// for pairs with `profit_in = "a"`
+    net_profit_in_currency: Option<f64>,
+    profit_percent: f64,
+    /// The larger price impact of the two legs' quotes, see `price_impact_pct`
+    price_impact_pct: f64,
+    /// How far each leg's price is from the off-chain reference, if one is configured
+    buy_deviation_pct: Option<f64>,
+    sell_deviation_pct: Option<f64>,
+    /// Blocks on top of `block_number` when reported, if waiting for `confirmations`
+    confirmations: Option<u64>,
+    /// Picks the log level and which notifiers are sent it, see `tiers`
+    tier: tier::Tier,
+}
+
+impl Opportunity {
+    /// A short hash of the block, pair, DEXes and trade size, so the same opportunity
+    /// gets the same ID in the logs, CSV, database and alerts. Without a block number
+    /// the timestamp stands in for it.
+    fn stable_id(&self) -> String {
+        let block = match self.block_number {
+            Some(number) => number.to_string(),
+            None => self.timestamp.to_rfc3339(),
+        };
+        let key = format!("{}|{}|{}|{}|{}", block, self.pair, self.buy_dex, self.sell_dex, self.amount_in);
+        ethers::utils::hex::encode(&ethers::utils::keccak256(key)[..8])
+    }
+}
+
+/// A single row of the opportunities CSV file
+#[derive(Debug, Serialize)]
+struct OpportunityRecord<'a> {
+    id: &'a str,
+    timestamp: String,
+    block_number: Option<u64>,
+    pair: &'a str,
+    amount_in: f64,
+    buy_dex: &'a str,
+    sell_dex: &'a str,
+    buy_price: f64,
+    sell_price: f64,
+    gross_profit: f64,
+    fees: f64,
+    slippage: f64,
+    gas_cost: f64,
+    gas_units: Option<u64>,
+    gas_price_gwei: Option<f64>,
+    gas_base_fee_gwei: Option<f64>,
+    gas_priority_fee_gwei: Option<f64>,
+    gas_cost_matic: Option<f64>,
+    net_profit: f64,
+    net_profit_in_currency: Option<f64>,
+    profit_percent: f64,
+    tier: tier::Tier,
+}
+
+impl<'a> OpportunityRecord<'a> {
+    fn new(opportunity: &'a Opportunity) -> Self {
+        let fees = opportunity.gas_estimate.and_then(|gas| gas.fees);
+        OpportunityRecord {
+            id: &opportunity.id,
+            timestamp: opportunity.timestamp.to_rfc3339(),
+            block_number: opportunity.block_number,
+            pair: &opportunity.pair,
+            amount_in: opportunity.amount_in,
+            buy_dex: &opportunity.buy_dex,
+            sell_dex: &opportunity.sell_dex,
+            buy_price: opportunity.buy_price,
+            sell_price: opportunity.sell_price,
+            gross_profit: opportunity.gross_profit,
+            fees: opportunity.fees,
+            slippage: opportunity.slippage,
+            gas_cost: opportunity.gas_cost,
+            gas_units: opportunity.gas_estimate.map(|gas| gas.units),
+            gas_price_gwei: opportunity.gas_estimate.map(|gas| gas.price_gwei),
+            gas_base_fee_gwei: fees.map(|fees| fees.base_fee_gwei),
+            gas_priority_fee_gwei: fees.map(|fees| fees.priority_fee_gwei),
+            gas_cost_matic: opportunity.gas_estimate.map(|gas| gas.cost_matic),
+            net_profit: opportunity.net_profit,
+            net_profit_in_currency: opportunity.net_profit_in_currency,
+            profit_percent: opportunity.profit_percent,
+            tier: opportunity.tier,
+        }
+    }
+}
+
+/// Rough gas usage of two Uniswap V2 swaps (one per leg)
+fn default_gas_units() -> u64 {
+    300_000
+}
+
+fn default_congestion_threshold() -> f64 {
+    0.8
+}
+
+fn default_max_retries() -> u32 {
+    3
+}
+
+fn default_retry_base_ms() -> u64 {
+    200
+}
+
+fn default_rpc_timeout_seconds() -> u64 {
+    10
+}
+
+fn default_enabled() -> bool {
+    true
+}
+
+fn default_max_concurrency() -> usize {
+    4
+}
+
+fn default_failover_after_failures() -> u32 {
+    3
+}
+
+fn default_provider_pool_size() -> usize {
+    1
+}
+
+fn default_breaker_cooldown_seconds() -> u64 {
+    300
+}
+
+fn default_display_decimals() -> usize {
+    4
+}
+
+fn default_max_recent_opportunities() -> usize {
+    100
+}
+
+fn default_bind_address() -> IpAddr {
+    Ipv4Addr::LOCALHOST.into()
+}
+
+/// USDC (PoS) on Polygon
+fn default_usdc() -> Address {
+    "0x2791bca1f2de4661ed88a30c99a7a9449aa84174".parse().expect("valid address")
+}
+
+const DEFAULT_CONFIG_PATH: &str = "./config/settings";
+
+/// Environment variables starting with this override settings
+const ENV_PREFIX: &str = "ARB_";
+
+/// Settings that are arrays of tables, which environment variables set as JSON
+const JSON_ENV_KEYS: [&str; 4] = ["pairs", "dexes", "cycles", "notifiers"];
+
+/// WMATIC, like native MATIC, always has 18 decimals
+const MATIC_DECIMALS: u32 = 18;
+
+/// Gas prices are reported in gwei, 1e-9 MATIC
+const GWEI_DECIMALS: u32 = 9;
+
+/// Used for `usdc` when its decimals weren't read at startup
+const USDC_DECIMALS: u32 = 6;
+
+/// The shortest gap jitter may leave between two ticks
+const MIN_INTERVAL: Duration = Duration::from_millis(100);
+
+/// Share of `amount_in` quoted alongside it to measure price impact
+const PRICE_IMPACT_REFERENCE_FRACTION: f64 = 0.001;
+
+/// How many DEX combinations each check logs when more than two DEXes were priced
+const RANKING_LOGGED: usize = 3;
+
 #[tokio::main]
 async fn main() -> Result<()> {
+    let cli = Cli::parse();
+
     // Load environment variables from .env file
     dotenv::dotenv().ok();
-    println!("Starting Polygon Arbitrage Bot...");
 
     // 1. CONFIGURATION MANAGEMENT 
-    let config_builder = config::Config::builder()
-        .add_source(config::File::with_name("./config/settings"))
-        .build()?;
-    let settings: Settings = config_builder.try_deserialize()?;
+    // An explicitly chosen file must exist. Otherwise the first settings file found in
+    // the usual places is used, and there may be none if ARB_* variables cover it.
+    let candidates = config_candidates(
+        cli.config.as_deref(),
+        xdg_config_home(),
+        std::env::current_exe().ok().and_then(|exe| exe.parent().map(Path::to_path_buf)),
+    );
+    let found = candidates.iter().find(|path| config_file_exists(path));
+    let config_path = found.unwrap_or(&candidates[0]).as_str();
+    let mut settings = load_settings(config_path, cli.config.is_some()).map_err(|e| {
+        if found.is_none() {
+            let tried: Vec<String> = candidates.iter().map(|path| format!("{}.toml", path)).collect();
+            eyre::eyre!(
+                "{}. No settings file was found, tried {}: pass --config with the path to your \
+                 settings file, or set every required ARB_* variable",
+                e,
+                tried.join(", ")
+            )
+        } else {
+            e.into()
+        }
+    })?;
+
+    // The file as loaded, before the command line overrides it, which reloads are compared
+    // against so flags are never mistaken for edits to the file
+    let loaded = settings.clone();
+
+    // Log filtering is controlled by RUST_LOG, e.g. RUST_LOG=debug. In JSON mode, for the
+    // spread stream and for --config-check stdout is reserved for the output, so logs go
+    // to stderr instead.
+    settings.spread_stream |= cli.spread_stream;
+    let logs = tracing_subscriber::fmt()
+        .with_env_filter(EnvFilter::try_from_default_env().unwrap_or_else(|_| EnvFilter::new("info")));
+    match settings.output_format {
+        output::OutputFormat::Text if !cli.config_check && !settings.spread_stream => logs.init(),
+        _ => logs.with_writer(std::io::stderr).init(),
+    }
+    info!("Starting Polygon Arbitrage Bot {}...", VERSION);
+    match found {
+        Some(path) => info!("Loaded settings from {}", path),
+        None => info!("No settings file found, using ARB_* environment variables only"),
+    }
+    if let Some(interval) = cli.interval {
+        settings.check_interval_seconds = interval;
+    }
+    settings.run_once |= cli.once;
+    if let Some(iterations) = cli.max_iterations {
+        settings.max_iterations = Some(iterations);
+    }
+    if let Some(seconds) = cli.max_runtime {
+        settings.max_runtime_seconds = Some(seconds);
+    }
+    if let Some(block) = cli.at_block {
+        settings.at_block = Some(block);
+    }
+    settings.run_once |= settings.at_block.is_some();
+    if let Some(path) = &cli.record_rpc {
+        settings.record_rpc = Some(path.clone());
+    }
+    // The benchmark is a run bounded by time rather than ticks
+    if let Some(seconds) = cli.benchmark {
+        settings.max_runtime_seconds = Some(seconds);
+    }
+
+    if let Err(problems) = validate(&settings) {
+        for problem in &problems {
+            error!("Invalid configuration: {}", problem);
+        }
+        return Err(eyre::eyre!("{} problem(s) found in the configuration", problems.len()));
+    }
+    if let Err(problems) = check_paths(&settings) {
+        for problem in &problems {
+            error!("Invalid path: {}", problem);
+        }
+        return Err(eyre::eyre!("{} path(s) in the configuration can't be used", problems.len()));
+    }
+
+    if cli.config_check {
+        settings.rpc_urls = rpc_urls(&settings);
+        println!("{}", serde_json::to_string_pretty(&redacted(settings))?);
+        info!("Configuration is valid.");
+        return Ok(());
+    }
 
-    if settings.dexes.len() < 2 {
-        panic!("Configuration must include at least two DEXes.");
+    if cli.backtest {
+        let Some(db_path) = &settings.database_path else {
+            return Err(eyre::eyre!("Backtesting needs `database_path` set in settings.toml"));
+        };
+        if !Path::new(db_path).exists() {
+            return Err(eyre::eyre!(
+                "No price history at {}: run the bot live with `database_path` set to record some first",
+                db_path
+            ));
+        }
+        let thresholds = if cli.thresholds.is_empty() {
+            vec![settings.minimum_profit_threshold]
+        } else {
+            cli.thresholds.clone()
+        };
+        return backtest::run(&settings, db_path, &thresholds);
     }
+
+    // Polling picks up a reloaded interval from the next tick on
+    let (pacing, pacing_updates) = tokio::sync::watch::channel(settings.pacing());
+    let reloader = if settings.run_once {
+        None
+    } else {
+        match reload::Reloader::watch(config_path, cli.interval, loaded, pacing) {
+            Ok(reloader) => Some(reloader),
+            Err(e) => {
+                warn!("Not watching {} for changes: {}", config_path, e);
+                None
+            }
+        }
+    };
     
     // 2. CONNECT TO POLYGON RPC NODE [cite: 12]
-    let rpc_url = std::env::var("POLYGON_RPC_URL")?;
-    let provider = Provider::<Http>::try_from(rpc_url)?;
-    let client = Arc::new(provider);
+    let rpc_urls = rpc_urls(&settings);
+    if rpc_urls.is_empty() {
+        return Err(eyre::eyre!("Set POLYGON_RPC_URL or `rpc_urls` in settings.toml"));
+    }
 
-    // Create contract instances for the two DEXes
-    let dex_a = &settings.dexes[0];
-    let dex_b = &settings.dexes[1];
-    let contract_a = IUniswapV2Router02::new(dex_a.router_address, client.clone());
-    let contract_b = IUniswapV2Router02::new(dex_b.router_address, client.clone());
+    // Metrics are always collected, but only exposed when a port is configured
+    let metrics = Arc::new(metrics::Metrics::new());
+    // One limiter for every endpoint, since a failover rotates between them rather than adding capacity
+    let limiter = settings.max_rpc_requests_per_second.and_then(NonZeroU32::new).map(rate_limit::limiter);
+    let clock: Arc<dyn Clock> = Arc::new(clock::SystemClock);
+    let benchmark = cli.benchmark.map(|_| benchmark::Benchmark::default());
 
-    // Main application loop
-    let mut interval = tokio::time::interval(Duration::from_secs(settings.check_interval_seconds));
-    loop {
-        interval.tick().await;
-        println!("\nChecking for arbitrage opportunities...");
-        
-        // Use a fixed amount of Token A (WETH) for the simulation
-        let amount_in = parse_units(settings.amount_in, settings.tokens.decimals_a)?.into();
+    if rpc_urls[0].starts_with("ws://") || rpc_urls[0].starts_with("wss://") {
+        // WebSocket nodes push new blocks, so re-price exactly once per block.
+        // The subscription is tied to one connection, so only the first URL is used.
+        if rpc_urls.len() > 1 {
+            warn!("Failover isn't supported over WebSocket, ignoring all but the first RPC URL.");
+        }
+        if settings.provider_pool_size > 1 {
+            warn!("`provider_pool_size` only applies to HTTP endpoints, using one WebSocket connection.");
+        }
+        let ws = RateLimited::new(Ws::connect(&rpc_urls[0]).await?, limiter, metrics.clone());
+        let client = Arc::new(Provider::new(ws));
+        let clients = vec![(endpoint_name(&rpc_urls[0]), client.clone())];
+        if benchmark.is_some() {
+            return run(settings, clients, back_to_back(), reloader, metrics, clock, benchmark.as_ref()).await;
+        }
+        let blocks = client.subscribe_blocks().await?;
+        info!("Connected over WebSocket, checking on every new block.");
+        run(settings, clients, blocks.map(|_| ()), reloader, metrics, clock, None).await
+    } else {
+        // HTTP nodes are polled on a fixed interval. Each gets its own pool of connections,
+        // which concurrent fetches take turns on.
+        let clients = rpc_urls
+            .iter()
+            .map(|url| {
+                let pool = (0..settings.provider_pool_size)
+                    .map(|_| Ok(RateLimited::new(Http::from_str(url)?, limiter.clone(), metrics.clone())))
+                    .collect::<Result<Vec<_>>>()?;
+                Ok((endpoint_name(url), Arc::new(Provider::new(pool::Pooled::new(pool)))))
+            })
+            .collect::<Result<Vec<_>>>()?;
+        if benchmark.is_some() {
+            return run(settings, clients, back_to_back(), reloader, metrics, clock, benchmark.as_ref()).await;
+        }
+        let ticks = polling_ticks(pacing_updates, clock.clone());
+        run(settings, clients, ticks, reloader, metrics, clock, None).await
+    }
+}
 
-        // 3. MULTI-DEX PRICE FETCHING [cite: 12]
-        let price_on_dex_a = get_price(&contract_a, amount_in, &settings.tokens).await;
-        let price_on_dex_b = get_price(&contract_b, amount_in, &settings.tokens).await;
+/// The RPC endpoints to use. POLYGON_RPC_URL may hold several comma-separated URLs
+/// and takes precedence over `rpc_urls`.
+fn rpc_urls(settings: &Settings) -> Vec<String> {
+    match std::env::var("POLYGON_RPC_URL") {
+        Ok(urls) => urls.split(',').map(|url| url.trim().to_string()).filter(|url| !url.is_empty()).collect(),
+        Err(_) => settings.rpc_urls.clone(),
+    }
+}
+
+/// A copy of `settings` that is safe to print: the webhook is hidden, and so is any
+/// path or query in the RPC URLs, where providers put API keys
+fn redacted(mut settings: Settings) -> Settings {
+    const REDACTED: &str = "<redacted>";
+    settings.rpc_urls = settings
+        .rpc_urls
+        .iter()
+        .map(|url| match reqwest::Url::parse(url) {
+            Ok(parsed) if parsed.path() == "/" && parsed.query().is_none() => url.clone(),
+            Ok(parsed) => format!("{}://{}/{}", parsed.scheme(), parsed.host_str().unwrap_or_default(), REDACTED),
+            Err(_) => REDACTED.to_string(),
+        })
+        .collect();
+    if settings.discord_webhook_url.is_some() {
+        settings.discord_webhook_url = Some(REDACTED.to_string());
+    }
+    settings.notifiers = settings.notifiers.iter().map(|notifier| notifier.redacted(REDACTED)).collect();
+    if let Some(source) = &mut settings.reference_price_source {
+        source.api_key = source.api_key.as_ref().map(|_| REDACTED.to_string());
+    }
+    settings
+}
+
+/// Yields immediately and then every interval, with each gap shifted randomly by up
+/// to the jitter so several bots polling the same provider don't stay in lockstep.
+/// The latest `pacing` is read before each gap, so reloaded settings apply at once.
+fn polling_ticks(pacing: tokio::sync::watch::Receiver<Pacing>, clock: Arc<dyn Clock>) -> impl Stream<Item = ()> {
+    let start = clock.now();
+    futures::stream::unfold((start, pacing), move |(deadline, pacing)| {
+        let clock = clock.clone();
+        async move {
+            // After falling behind, e.g. during a breaker cooldown, tick once rather than catching up
+            let deadline = deadline.max(clock.now());
+            tokio::time::sleep_until(deadline).await;
+            let Pacing { interval, jitter } = *pacing.borrow();
+            Some(((), (deadline + jittered(interval, jitter), pacing)))
+        }
+    })
+}
 
-        if let (Ok(price_a), Ok(price_b)) = (price_on_dex_a, price_on_dex_b) {
-            println!("Price on {}: 1 WETH -> {:.4} USDC", dex_a.name, price_a);
-            println!("Price on {}: 1 WETH -> {:.4} USDC", dex_b.name, price_b);
+/// Yields as soon as it is polled, so `--benchmark` checks back to back
+fn back_to_back() -> impl Stream<Item = ()> {
+    futures::stream::repeat(())
+}
 
-            // 4. ARBITRAGE OPPORTUNITY DETECTION [cite: 13] & PROFIT CALCULATION [cite: 14]
-            check_opportunity(&settings, dex_a, dex_b, price_a, price_b);
-        } else {
-            eprintln!("Error fetching prices from one or both DEXes.");
+/// `interval` plus a random offset within ±`jitter`, never shorter than `MIN_INTERVAL`
+fn jittered(interval: Duration, jitter: Duration) -> Duration {
+    if jitter.is_zero() {
+        return interval;
+    }
+    let offset = rand::thread_rng().gen_range(-jitter.as_secs_f64()..=jitter.as_secs_f64());
+    Duration::from_secs_f64((interval.as_secs_f64() + offset).max(MIN_INTERVAL.as_secs_f64()))
+}
+
+/// The symbol a token is logged under: the configured one, else the `native`
+/// (wrapper, symbol) pair's symbol for the wrapper, else the one read from its contract
+fn token_symbol(
+    configured: Option<String>,
+    token: Address,
+    native: Option<(Address, &str)>,
+    symbols: &HashMap<Address, String>,
+) -> Option<String> {
+    configured
+        .or_else(|| native.filter(|&(wrapper, _)| wrapper == token).map(|(_, symbol)| symbol.to_string()))
+        .or_else(|| symbols.get(&token).cloned())
+}
+
+/// Describes every configured decimals value that disagrees with what its token's contract
+/// reports, e.g. `decimals_b = 6` for an 18-decimal token, which would skew every price
+/// by a factor of 10^12. Tokens whose decimals couldn't be read aren't compared.
+fn decimals_mismatches(
+    settings: &Settings,
+    on_chain: impl Fn(Address) -> Option<u32>,
+    symbols: &HashMap<Address, String>,
+) -> Vec<String> {
+    let pairs = settings.pairs.iter().flat_map(|pair| {
+        [
+            (format!("{} `decimals_a`", pair.label), pair.token_a, &pair.symbol_a, pair.decimals_a),
+            (format!("{} `decimals_b`", pair.label), pair.token_b, &pair.symbol_b, pair.decimals_b),
+        ]
+    });
+    let cycles = settings.cycles.iter().map(|cycle| {
+        (format!("{} `decimals`", cycle.label), cycle.tokens[0], &cycle.symbol, cycle.decimals)
+    });
+    let currency = settings.profit_currency.iter().map(|currency| {
+        ("`profit_currency.decimals`".to_string(), currency.token, &None, currency.decimals)
+    });
+
+    let mut problems = Vec::new();
+    for (setting, token, symbol, configured) in pairs.chain(cycles).chain(currency) {
+        let Some(actual) = on_chain(token).filter(|&actual| actual != configured) else {
+            continue;
+        };
+        let name = symbol.as_ref().or_else(|| symbols.get(&token));
+        problems.push(match name {
+            Some(name) => format!("{} is {}, but {} ({:?}) has {} decimals", setting, configured, name, token, actual),
+            None => format!("{} is {}, but {:?} has {} decimals", setting, configured, token, actual),
+        });
+    }
+    problems
+}
+
+/// The alert channels listed under `notifiers`, plus Telegram and Discord when their
+/// credentials are provided in the environment (or `discord_webhook_url`) and no entry
+/// of that kind is listed. A listed entry already falls back to those credentials, so
+/// building both would alert twice, once ignoring the entry's `min_tier`. Variables are
+/// read with `env`.
+fn notifier_channels(settings: &Settings, env: impl Fn(&str) -> Option<String>) -> Vec<notify::Channel> {
+    let mut channels = Vec::new();
+    for entry in &settings.notifiers {
+        match entry.build(settings.display_decimals, &env) {
+            Some(channel) => channels.push(channel),
+            None => warn!("Skipping a Telegram notifier without a bot token and chat id."),
+        }
+    }
+    let listed = |kind: fn(&notify::NotifierConfig) -> bool| settings.notifiers.iter().any(|entry| kind(&entry.config));
+    if !listed(|config| matches!(config, notify::NotifierConfig::Telegram { .. })) {
+        if let Some(telegram) = notify::Telegram::from_env(settings.display_decimals, &env) {
+            channels.push(notify::Channel::all(Arc::new(telegram)));
+        }
+    }
+    if !listed(|config| matches!(config, notify::NotifierConfig::Discord { .. })) {
+        let webhook_url = env("DISCORD_WEBHOOK_URL").or_else(|| settings.discord_webhook_url.clone());
+        if let Some(webhook_url) = webhook_url {
+            let discord = notify::Discord::new(webhook_url, settings.display_decimals);
+            channels.push(notify::Channel::all(Arc::new(discord)));
         }
     }
+    channels
 }
 
-/// Fetches the price of Token A in terms of Token B from a single DEX
-async fn get_price(contract: &IUniswapV2Router02<Provider<Http>>, amount_in: U256, tokens: &Tokens) -> Result<f64> {
-    let path = vec![tokens.weth, tokens.usdc];
-    let amounts_out = contract.get_amounts_out(amount_in, path).call().await?;
-    
-    // The second element in the returned array is the output amount
-    if let Some(amount) = amounts_out.get(1) {
-        // Convert from WEI/Satoshi format to a readable float
-        Ok(amount.as_u128() as f64 / 10f64.powi(tokens.decimals_b as i32))
-    } else {
-        Err(eyre::eyre!("Could not get amount out from DEX"))
+/// Identifies an RPC endpoint in logs by its host, keeping any API key in the path out of them
+fn endpoint_name(url: &str) -> String {
+    reqwest::Url::parse(url)
+        .ok()
+        .and_then(|url| url.host_str().map(str::to_string))
+        .unwrap_or_else(|| "<invalid url>".to_string())
+}
+
+/// Logs the configured transfer taxes, and drops the pairs and cycles through a token
+/// taxed more than `max_tax_bps`
+fn exclude_taxed_tokens(settings: &mut Settings, symbols: &HashMap<Address, String>) {
+    let mut excluded_tokens = Vec::new();
+    for tax in &settings.token_taxes {
+        let name = symbols.get(&tax.token).cloned().unwrap_or_else(|| format!("{:?}", tax.token));
+        match settings.max_tax_bps {
+            Some(max) if settings.tax_bps(tax.token) > max => {
+                warn!(
+                    "{} takes a {} bps transfer tax, over the {} bps maximum, so it isn't traded",
+                    name, tax.tax_bps, max
+                );
+                excluded_tokens.push(tax.token);
+            }
+            _ => info!("{} takes a {} bps transfer tax, deducted from profits", name, tax.tax_bps),
+        }
     }
+    let too_taxed = |token: &Address| excluded_tokens.contains(token);
+    let (pairs, excluded): (Vec<Pair>, Vec<Pair>) = std::mem::take(&mut settings.pairs)
+        .into_iter()
+        .partition(|pair| !pair.path(false).iter().any(too_taxed));
+    let (cycles, excluded_cycles): (Vec<Cycle>, Vec<Cycle>) =
+        std::mem::take(&mut settings.cycles).into_iter().partition(|cycle| !cycle.tokens.iter().any(too_taxed));
+    for label in excluded.iter().map(|pair| &pair.label).chain(excluded_cycles.iter().map(|cycle| &cycle.label)) {
+        warn!("Skipping {}, it trades through a token taxed over `max_tax_bps`.", label);
+    }
+    settings.pairs = pairs;
+    settings.cycles = cycles;
 }
 
-/// Compares prices and logs a potential arbitrage opportunity if one exists
-fn check_opportunity(settings: &Settings, dex_a: &Dex, dex_b: &Dex, price_a: f64, price_b: f64) {
-    // Opportunity: Buy on the cheaper DEX, sell on the more expensive one
-    let (buy_dex, sell_dex, buy_price, sell_price) = if price_a < price_b {
-        (dex_a, dex_b, price_a, price_b)
-    } else {
-        (dex_b, dex_a, price_b, price_a)
-    };
-    
-    let gross_profit = (sell_price - buy_price) * settings.amount_in;
-    
-    // 5. SIMULATED PROFIT CALCULATION (including gas cost) [cite: 10]
-    let simulated_profit = gross_profit - settings.simulated_gas_cost_usdc;
-
-    if simulated_profit > settings.minimum_profit_threshold {
-        println!("\n!!!!!!!!!!!!!!!!!!!!!!!!!!!!!!!!!!!!!!!!!!!!!!!!!");
-        println!("!!! Arbitrage Opportunity Detected!           !!!");
-        println!("!!!!!!!!!!!!!!!!!!!!!!!!!!!!!!!!!!!!!!!!!!!!!!!!!");
-        println!("  - Action: BUY {} WETH on {}", settings.amount_in, buy_dex.name);
-        println!("  - Action: SELL {} WETH on {}", settings.amount_in, sell_dex.name);
-        println!("  - Est. Gross Profit: {:.4} USDC", gross_profit);
-        println!("  - Simplified Gas Cost: -{:.4} USDC", settings.simulated_gas_cost_usdc);
-        println!("  - SIMULATED NET PROFIT: {:.4} USDC", simulated_profit);
-        println!("!!!!!!!!!!!!!!!!!!!!!!!!!!!!!!!!!!!!!!!!!!!!!!!!!\n");
+/// Runs the main loop, scanning every pair each time `ticks` yields, or just for
+/// the first tick when `run_once` is set. `reloader` applies settings file changes, and
+/// a `benchmark` collects quote timings to report when the run ends.
+async fn run<M: Middleware + 'static>(
+    mut settings: Settings,
+    clients: Vec<(String, Arc<M>)>,
+    ticks: impl Stream<Item = ()>,
+    mut reloader: Option<reload::Reloader>,
+    metrics: Arc<metrics::Metrics>,
+    clock: Arc<dyn Clock>,
+    benchmark: Option<&benchmark::Benchmark>,
+) -> Result<()> {
+    let dexes = settings.enabled_dexes();
+    let names: Vec<&str> = dexes.iter().map(|dex| dex.name.as_str()).collect();
+    info!("Active DEXes: {}", names.join(", "));
+    for dex in settings.dexes.iter().filter(|dex| !dex.enabled) {
+        info!("DEX {} is disabled, skipping it.", dex.name);
+    }
+
+    // A wrong address would otherwise only show up as every tick failing
+    let missing = registry::missing_contracts(&settings, &dexes, clients[0].1.as_ref()).await;
+    if !missing.is_empty() {
+        for problem in &missing {
+            error!("Invalid address: {}", problem);
+        }
+        return Err(eyre::eyre!("{} configured address(es) have no contract deployed", missing.len()));
+    }
+
+    // Token metadata is the same on every endpoint, so read it once; contracts are bound per endpoint
+    let decimals = Arc::new(registry::token_decimals(&settings, clients[0].1.clone()).await);
+    let rpc_log = match &settings.record_rpc {
+        Some(path) => {
+            info!("Recording every getAmountsOut call to {}", path);
+            Some(Arc::new(rpc_log::RpcLog::create(path).map_err(|e| eyre::eyre!("Error opening {}: {}", path, e))?))
+        }
+        None => None,
+    };
+    let registries = clients
+        .into_iter()
+        .map(|(name, client)| {
+            (name, registry::MarketRegistry::new(&dexes, client, decimals.clone(), rpc_log.clone()))
+        })
+        .collect();
+    let mut endpoints = failover::Failover::new(registries, settings.failover_after_failures);
+    endpoints.log_endpoints();
+
+    // Prefer the decimals reported on-chain over the configured ones, and the configured
+    // symbols over those reported on-chain
+    let markets = endpoints.active();
+    let symbols = registry::token_symbols(&settings, markets.client().clone()).await;
+    let mismatches = decimals_mismatches(&settings, |token| markets.decimals(token), &symbols);
+    for mismatch in &mismatches {
+        error!("Decimals mismatch: {}", mismatch);
+    }
+    if !mismatches.is_empty() {
+        if settings.on_decimals_mismatch == DecimalsMismatch::Abort {
+            return Err(eyre::eyre!("{} configured decimals disagree with their token contracts", mismatches.len()));
+        }
+        warn!("Using the on-chain decimals; set `on_decimals_mismatch = \"abort\"` to refuse to start instead.");
+    }
+    let native = settings.native_symbol.as_deref().map(|symbol| (settings.wmatic, symbol));
+    for pair in &mut settings.pairs {
+        pair.decimals_a = markets.decimals(pair.token_a).unwrap_or(pair.decimals_a);
+        pair.decimals_b = markets.decimals(pair.token_b).unwrap_or(pair.decimals_b);
+        pair.symbol_a = token_symbol(pair.symbol_a.take(), pair.token_a, native, &symbols);
+        pair.symbol_b = token_symbol(pair.symbol_b.take(), pair.token_b, native, &symbols);
+        if pair.invert {
+            info!("Quoting {} inverted, {} -> {}.", pair.label, pair.symbol_b(), pair.symbol_a());
+        }
+    }
+    settings.pairs = std::mem::take(&mut settings.pairs).into_iter().map(Pair::oriented).collect();
+    for cycle in &mut settings.cycles {
+        cycle.decimals = markets.decimals(cycle.tokens[0]).unwrap_or(cycle.decimals);
+        cycle.symbol = token_symbol(cycle.symbol.take(), cycle.tokens[0], native, &symbols);
+    }
+    exclude_taxed_tokens(&mut settings, &symbols);
+    if settings.pairs.is_empty() {
+        return Err(eyre::eyre!("Every pair trades through a token taxed over `max_tax_bps`, nothing to scan"));
+    }
+    let mut retry = settings.retry_policy();
+    let freshness = settings.staleness_threshold_seconds.map(|seconds| {
+        let dex_names = dexes.iter().map(|dex| dex.name.as_str());
+        let pair_labels = settings.pairs.iter().map(|pair| pair.label.as_str());
+        freshness::Freshness::new(dex_names, pair_labels, Duration::from_secs(seconds), clock.now())
+    });
+    let reference_prices = settings
+        .reference_price_source
+        .clone()
+        .map(|source| reference::ReferencePrices::new(source, retry.timeout))
+        .transpose()?;
+
+    // The CSV file and the price/opportunity history for backtesting, among others
+    let sinks = sink::from_settings(&settings)?;
+
+    // The simulated PnL of taking every opportunity, carried over from earlier runs if saved
+    let ledger = match &settings.paper_ledger_path {
+        Some(path) => ledger::PaperLedger::load(path)
+            .map_err(|e| eyre::eyre!("Error loading the paper ledger {}: {}", path, e))?,
+        None => ledger::PaperLedger::default(),
+    };
+    let ledger: ledger::SharedLedger = Arc::new(std::sync::Mutex::new(ledger));
+
+    // Serve recent opportunities over HTTP alongside the main loop
+    let recent = http::RecentOpportunities::default();
+    let health = http::SharedHealth::default();
+    // Scanning can be paused for maintenance by SIGUSR1 or over HTTP without a restart
+    let pause = pause::Pause::default();
+    if let Err(e) = pause::listen_for_signal(pause.clone()) {
+        warn!("Not listening for SIGUSR1 to pause scanning: {}", e);
+    }
+    if let Some(port) = settings.http_port {
+        let addr = SocketAddr::new(settings.bind_address, port);
+        let (recent, health, ledger, pause) = (recent.clone(), health.clone(), ledger.clone(), pause.clone());
+        tokio::spawn(async move {
+            if let Err(e) = http::serve(addr, recent, health, ledger, pause).await {
+                error!("HTTP server on {} stopped: {}", addr, e);
+            }
+        });
+    }
+
+    if let Some(port) = settings.metrics_port {
+        let (addr, metrics) = (SocketAddr::new(settings.bind_address, port), metrics.clone());
+        tokio::spawn(async move {
+            if let Err(e) = metrics::serve(addr, metrics).await {
+                error!("Metrics server on {} stopped: {}", addr, e);
+            }
+        });
+    }
+
+    let notifiers = notifier_channels(&settings, |name| std::env::var(name).ok());
+    for channel in &notifiers {
+        match channel.min_tier {
+            tier::Tier::Info => info!("{} alerts enabled.", channel.notifier.name()),
+            min_tier => info!("{} alerts enabled for {} opportunities and above.", channel.notifier.name(), min_tier),
+        }
+    }
+
+    let executor = execute::for_mode(settings.execution_mode);
+    info!("Execution mode: {}.", executor.name());
+
+    // Every detected opportunity is logged and handed to all configured outputs
+    let mut alerts = settings
+        .alert_cooldown_seconds
+        .map(|seconds| dedupe::AlertFilter::new(Duration::from_secs(seconds)));
+    let mut publish = |settings: &Settings, opportunity: &Opportunity, tick: u64| {
+        report_opportunity(settings, opportunity);
+        metrics.record_opportunity(&opportunity.pair, opportunity.net_profit);
+        let alert = alerts
+            .as_mut()
+            .is_none_or(|alerts| alerts.should_alert(opportunity, tick, clock.now()));
+        if alert {
+            notify::notify_all(&notifiers, opportunity);
+        } else {
+            debug!(pair = %opportunity.pair, "Opportunity already alerted, not sending it again");
+        }
+        http::record(&recent, opportunity.clone(), settings.max_recent_opportunities);
+    };
+
+    // A hung await would otherwise leave the bot silently idle
+    let heartbeat = watchdog::Heartbeat::new(clock.unix_seconds());
+    if let Some(seconds) = settings.watchdog_timeout_seconds {
+        watchdog::spawn(heartbeat.clone(), Duration::from_secs(seconds), settings.watchdog_exit, clock.clone());
+    }
+
+    // Main application loop
+    tokio::pin!(ticks);
+    // Created once so a Ctrl+C that arrives mid-iteration is still seen at the next tick
+    let shutdown = tokio::signal::ctrl_c();
+    tokio::pin!(shutdown);
+    let mut single_run_failed = false;
+    let mut stats = stats::Stats::default();
+    let mut spread_windows = settings.spread_window.map(spread_window::SpreadWindows::new);
+    let mut breaker = settings
+        .max_consecutive_failures
+        .map(|failures| breaker::CircuitBreaker::new(failures, Duration::from_secs(settings.breaker_cooldown_seconds)));
+    // Bounded runs, e.g. smoke tests in CI, stop between ticks once either limit is reached
+    let max_iterations = settings.max_iterations.filter(|&iterations| iterations > 0);
+    let runtime_deadline = settings
+        .max_runtime_seconds
+        .filter(|&seconds| seconds > 0)
+        .map(|seconds| clock.now() + Duration::from_secs(seconds));
+    let runtime_limit = async move {
+        match runtime_deadline {
+            Some(deadline) => tokio::time::sleep_until(deadline).await,
+            None => std::future::pending().await,
+        }
+    };
+    tokio::pin!(runtime_limit);
+    let mut limit_reached = false;
+    // A past block is already as confirmed as it will get
+    let mut confirmations = settings
+        .confirmations
+        .filter(|_| settings.at_block.is_none())
+        .map(confirm::Confirmations::new);
+    if let Some(block) = settings.at_block {
+        // Nodes only report the current gas price, there's no asking for a past block's
+        info!("Quoting against block {} instead of the latest; gas is still priced at the latest fees.", block);
+    } else if settings.price_confirmations > 0 {
+        info!(
+            "Quoting {} blocks behind the latest; gas is still priced at the latest fees.",
+            settings.price_confirmations
+        );
+    }
+    if settings.warmup_ticks > 0 {
+        info!("Warming up for {} checks before reporting opportunities.", settings.warmup_ticks);
+    }
+    if let Some(seconds) = settings.max_runtime_seconds.filter(|_| benchmark.is_some()) {
+        info!("Benchmarking for {}s, checking back to back.", seconds);
+    }
+    let started = clock.now();
+    loop {
+        tokio::select! {
+            tick = ticks.next() => {
+                if tick.is_none() {
+                    return Err(eyre::eyre!("Block subscription ended unexpectedly"));
+                }
+            }
+            _ = &mut shutdown => break,
+            _ = &mut runtime_limit => {
+                info!("Reached max_runtime_seconds, stopping.");
+                limit_reached = true;
+                break;
+            }
+        }
+        if reloader.as_mut().is_some_and(|reloader| reloader.reload(&mut settings)) {
+            retry = settings.retry_policy();
+        }
+        // Paused checks aren't counted, but keep the watchdog from seeing a stall
+        if pause.is_paused() {
+            info!("Paused, skipping this check.");
+            heartbeat.beat(clock.unix_seconds());
+            continue;
+        }
+        info!(rpc = %endpoints.active_name(), "Checking for arbitrage opportunities...");
+        let mut tick = output::TickResult::new(stats.ticks() + 1, endpoints.active_name());
+        // Spread windows and stats start out empty, so early ticks only collect samples
+        let warming_up = tick.tick <= settings.warmup_ticks;
+
+        let markets = endpoints.active();
+        // Fetched once per tick: the block number stamps every price and opportunity (its
+        // hash too when waiting for confirmations), and congestion scales every priority fee
+        let (block, utilization, gas_price, fees) = futures::join!(
+            async {
+                let client = markets.client().as_ref();
+                match (settings.at_block, settings.confirmations) {
+                    (Some(number), _) => Ok((number, None)),
+                    (None, Some(_)) => {
+                        get_latest_block(client, retry.timeout).await.map(|(number, hash)| (number, Some(hash)))
+                    }
+                    (None, None) => get_block_number(client, retry.timeout).await.map(|number| (number, None)),
+                }
+            },
+            async {
+                match settings.congestion_factor {
+                    Some(_) => Some(get_block_utilization(markets.client().as_ref(), retry.timeout).await),
+                    None => None,
+                }
+            },
+            async {
+                match settings.max_gas_price_gwei {
+                    Some(_) => Some(get_gas_price_gwei(markets.client().as_ref(), retry.timeout).await),
+                    None => None,
+                }
+            },
+            get_gas_fees(markets.client().as_ref(), retry),
+        );
+        let (latest, block_hash) = match block {
+            Ok((number, hash)) => (Some(number), hash),
+            Err(e) => {
+                warn!("Error fetching the block number: {}", e);
+                (None, None)
+            }
+        };
+        // Without the latest block number the lagging block is unknown, so quote the latest
+        let price_block = settings.price_block(latest);
+        let block_number = price_block.or(latest);
+        tick.block_number = block_number;
+        // Shared by every gas estimate priced in USDC, as of the block the pairs are quoted at
+        let matic_price = get_matic_price_usdc(markets, &settings, retry, price_block.map(BlockId::from)).await;
+        let matic_price_usdc = match matic_price {
+            Ok(price) => Some(price),
+            Err(e) => {
+                warn!("Error quoting MATIC in USDC: {}", e);
+                settings.fallback_matic_price_usdc
+            }
+        };
+        // A past block's pools are measured against that block's time, not the clock's
+        let block_timestamp = match price_block.filter(|_| settings.max_pool_idle_seconds.is_some()) {
+            Some(number) => match get_block_timestamp(markets.client().as_ref(), number, retry.timeout).await {
+                Ok(timestamp) => Some(timestamp),
+                Err(e) => {
+                    warn!("Error fetching the timestamp of block {}: {}", number, e);
+                    None
+                }
+            },
+            None => None,
+        };
+        let utilization = match utilization.transpose() {
+            Ok(utilization) => utilization,
+            Err(e) => {
+                warn!("Error fetching the latest block's gas usage: {}", e);
+                None
+            }
+        };
+        let gas_fees = match fees {
+            Ok(fees) => Some(fees),
+            Err(e) => {
+                warn!("Error fetching the gas price, using simulated gas costs: {}", e);
+                None
+            }
+        };
+        let network = Network { matic_price_usdc, utilization, gas_price: gas_fees };
+        // Pairs are still priced, so spreads keep being logged and recorded
+        let gas_too_high = match (gas_price.transpose(), settings.max_gas_price_gwei) {
+            (Ok(Some(gwei)), Some(max)) if gwei > max => {
+                info!(
+                    "Gas price is {:.1} gwei, above the {} gwei maximum, not reporting opportunities this tick",
+                    gwei, max
+                );
+                true
+            }
+            (Err(e), _) => {
+                warn!("Error fetching the gas price: {}", e);
+                false
+            }
+            _ => false,
+        };
+        if let Some(utilization) = utilization {
+            let multiplier = network.priority_fee_multiplier(&settings);
+            if multiplier == 1.0 {
+                debug!("Latest block is {:.0}% full", utilization * 100.0);
+            } else {
+                info!("Latest block is {:.0}% full, scaling priority fees by {}x", utilization * 100.0, multiplier);
+            }
+        }
+        let scanner = Scanner {
+            settings: &settings,
+            markets,
+            block_number,
+            at_block: price_block.map(BlockId::from),
+            block_timestamp,
+            network,
+            retry,
+            sinks: &sinks,
+            clock: clock.as_ref(),
+            metrics: &metrics,
+            benchmark,
+            freshness: freshness.as_ref(),
+            reference_prices: reference_prices.as_ref(),
+        };
+        // Pairs are scanned concurrently, then handled in config order so output stays stable
+        let mut scans: Vec<_> = futures::stream::iter(settings.pairs.iter().enumerate())
+            .map(|(i, pair)| {
+                let scanner = &scanner;
+                async move { (i, pair, scanner.scan_pair(pair).await) }
+            })
+            .buffer_unordered(settings.max_concurrency)
+            .collect()
+            .await;
+        scans.sort_by_key(|&(i, _, _)| i);
+        let mut detected = Vec::new();
+        for (_, pair, scan) in scans {
+            match scan {
+                Ok(scans) => {
+                    tick.scanned += 1;
+                    if settings.reports_each_size() {
+                        log_trade_sizes(pair, &scans, settings.display_decimals);
+                    }
+                    for scan in scans {
+                        stats.record_spread(scan.spread_pct);
+                        if let (Some(near_miss), Some(keep)) = (scan.near_miss, settings.top_near_misses) {
+                            stats.record_near_miss(near_miss, keep);
+                        }
+                        let average = spread_windows
+                            .as_mut()
+                            .map(|windows| windows.record(&pair.label, scan.trade_size, scan.spread_pct));
+                        if let Some(opportunity) = scan.opportunity {
+                            // A blip on one tick isn't worth acting on unless the spread has held up
+                            if let (Some(average), Some(needed)) = (average, scan.spread_needed_pct) {
+                                if average < needed {
+                                    info!(
+                                        pair = %pair.label,
+                                        "Ignoring opportunity, the spread averaged {:.3}% over recent checks but needs {:.3}%",
+                                        average, needed
+                                    );
+                                    continue;
+                                }
+                            }
+                            if warming_up {
+                                info!(pair = %pair.label, "Warming up, not reporting opportunity");
+                                continue;
+                            }
+                            if gas_too_high {
+                                info!(pair = %pair.label, "Gas too high, not reporting opportunity");
+                                continue;
+                            }
+                            detected.push(opportunity);
+                        }
+                    }
+                }
+                Err(e) => {
+                    tick.errors += 1;
+                    stats.record_fetch_error();
+                    warn!(pair = %pair.label, "Error scanning pair: {}", e);
+                    health.lock().unwrap().record_error(&e);
+                }
+            }
+        }
+        if settings.triangular && settings.at_block.is_some() {
+            info!("Skipping triangular cycles, --at-block only replays pairs");
+        } else if settings.triangular {
+            for cycle in &settings.cycles {
+                for (dex, _) in markets.contracts() {
+                    let block = price_block.map(BlockId::from);
+                    let result = check_triangular(&settings, dex, cycle, markets, block, network, retry).await;
+                    if result.is_ok() {
+                        tick.scanned += 1;
+                    }
+                    match result {
+                        Ok(opportunities) => {
+                            for mut opportunity in opportunities {
+                                if warming_up {
+                                    info!(pair = %opportunity.pair, "Warming up, not reporting opportunity");
+                                    continue;
+                                }
+                                if gas_too_high {
+                                    info!(pair = %opportunity.pair, "Gas too high, not reporting opportunity");
+                                    continue;
+                                }
+                                opportunity.block_number = block_number;
+                                detected.push(opportunity);
+                            }
+                        }
+                        Err(e) => {
+                            tick.errors += 1;
+                            stats.record_fetch_error();
+                            warn!(cycle = %cycle.label, dex = %dex.name, "Error checking cycle: {}", e);
+                            health.lock().unwrap().record_error(&e);
+                        }
+                    }
+                }
+            }
+        }
+
+        let ready = match &mut confirmations {
+            Some(confirmations) => {
+                hold_for_confirmations(confirmations, detected, block_hash);
+                match block_number {
+                    Some(latest) => confirmed(confirmations, latest, markets.client().as_ref(), retry.timeout).await,
+                    None => Vec::new(),
+                }
+            }
+            None => detected,
+        };
+        for mut opportunity in ready {
+            opportunity.id = opportunity.stable_id();
+            opportunity.tier = settings.tier_of(&opportunity);
+            tick.opportunities += 1;
+            stats.record_opportunity(opportunity.net_profit);
+            publish(&settings, &opportunity, tick.tick);
+            sink::record_opportunity(&sinks, &opportunity).await;
+            execute::submit(executor.as_ref(), &opportunity).await;
+            let mut ledger = ledger.lock().unwrap();
+            let profit = opportunity.net_profit_in_currency.unwrap_or(opportunity.net_profit);
+            ledger.record(profit, profit_currency(&settings, &opportunity));
+            if let Some(path) = &settings.paper_ledger_path {
+                if let Err(e) = ledger.save(path) {
+                    warn!("Error saving the paper ledger to {}: {}", path, e);
+                }
+            }
+        }
+
+        // A tick where no pair could be scanned counts against the active endpoint
+        let any_scanned = tick.scanned > 0;
+        health.lock().unwrap().record_tick(any_scanned);
+        if any_scanned {
+            endpoints.record_success();
+        } else {
+            endpoints.record_failure();
+        }
+
+        stats.record_tick();
+        heartbeat.beat(clock.unix_seconds());
+        if warming_up && tick.tick == settings.warmup_ticks {
+            info!("Warmup complete after {} checks, reporting opportunities from now on.", tick.tick);
+        }
+        if settings.output_format == output::OutputFormat::Json {
+            output::emit(output::Event::Tick(&tick));
+        }
+        if settings.summary_every_n_ticks.is_some_and(|n| stats.ticks() % n == 0) {
+            stats.log_summary(settings.display_decimals);
+            ledger.lock().unwrap().log_summary(settings.display_decimals);
+        }
+
+        if settings.run_once {
+            single_run_failed = !any_scanned;
+            break;
+        }
+        if max_iterations.is_some_and(|iterations| stats.ticks() >= iterations) {
+            info!("Reached max_iterations ({}), stopping.", stats.ticks());
+            limit_reached = true;
+            break;
+        }
+
+        if let Some(breaker) = &mut breaker {
+            let cooldown = if any_scanned {
+                breaker.record_success();
+                None
+            } else {
+                breaker.record_failure()
+            };
+            health.lock().unwrap().breaker_open = cooldown.is_some();
+            if let Some(cooldown) = cooldown {
+                warn!(
+                    "{} checks failed in a row, backing off for {}s",
+                    breaker.consecutive_failures(),
+                    cooldown.as_secs()
+                );
+                heartbeat.excuse_until(clock.unix_seconds() + cooldown.as_secs());
+                tokio::select! {
+                    _ = tokio::time::sleep(cooldown) => {}
+                    _ = &mut shutdown => break,
+                    _ = &mut runtime_limit => {
+                        info!("Reached max_runtime_seconds, stopping.");
+                        limit_reached = true;
+                        break;
+                    }
+                }
+            }
+        }
+    }
+
+    // The CSV writer flushes after every row; the database may still have queued rows
+    info!("Shutting down.");
+    if settings.summary_every_n_ticks.is_some() || limit_reached {
+        stats.log_summary(settings.display_decimals);
+        ledger.lock().unwrap().log_summary(settings.display_decimals);
+    }
+    if let Some(benchmark) = benchmark {
+        benchmark.report(stats.ticks(), clock.now() - started).log();
+    }
+    for sink in &sinks {
+        sink.close();
+    }
+    // Schedulers like cron treat the non-zero exit this causes as a failed run
+    if single_run_failed {
+        return Err(eyre::eyre!("No pair could be priced during the single run"));
+    }
+    Ok(())
+}
+
+/// Loads `Settings` from a TOML file (the extension may be omitted), overridden by
+/// any `ARB_`-prefixed environment variables
+fn load_settings(path: &str, required: bool) -> Result<Settings, BotError> {
+    settings_from(config::File::with_name(path).required(required), std::env::vars())
+}
+
+/// Layers environment variables over a settings source. `ARB_MINIMUM_PROFIT_THRESHOLD`
+/// sets `minimum_profit_threshold`, `__` reaches into tables (`ARB_AMOUNT_SWEEP__MIN`),
+/// `ARB_RPC_URLS` is comma-separated, and the arrays of tables take JSON, e.g.
+/// `ARB_DEXES='[{"name": "QuickSwap", "router_address": "0x..."}]'`.
+fn settings_from<S>(file: S, vars: impl IntoIterator<Item = (String, String)>) -> Result<Settings, BotError>
+where
+    S: config::Source + Send + Sync + 'static,
+{
+    let mut builder = config::Config::builder().add_source(file);
+    let mut plain_vars = HashMap::new();
+    for (name, value) in vars {
+        match JSON_ENV_KEYS.iter().find(|key| name == format!("{}{}", ENV_PREFIX, key.to_uppercase())) {
+            Some(key) => {
+                let json = format!("{{\"{}\": {}}}", key, value);
+                builder = builder.add_source(config::File::from_str(&json, config::FileFormat::Json));
+            }
+            None => {
+                plain_vars.insert(name, value);
+            }
+        }
+    }
+    let env = config::Environment::with_prefix(ENV_PREFIX.trim_end_matches('_'))
+        .prefix_separator("_")
+        .separator("__")
+        .try_parsing(true)
+        .list_separator(",")
+        .with_list_parse_key("rpc_urls")
+        .source(Some(plain_vars));
+    Ok(builder.add_source(env).build()?.try_deserialize()?)
+}
+
+/// Where to look for the settings file, in order: just `explicit` if `--config` was
+/// given, otherwise `./config/settings`, `arb-bot/settings` under the XDG config
+/// directory, and `settings` next to the executable
+fn config_candidates(
+    explicit: Option<&str>,
+    xdg_config_home: Option<PathBuf>,
+    exe_dir: Option<PathBuf>,
+) -> Vec<String> {
+    if let Some(path) = explicit {
+        return vec![path.to_string()];
+    }
+    let mut candidates = vec![DEFAULT_CONFIG_PATH.to_string()];
+    candidates.extend(xdg_config_home.map(|dir| dir.join("arb-bot").join("settings").display().to_string()));
+    candidates.extend(exe_dir.map(|dir| dir.join("settings").display().to_string()));
+    candidates
+}
+
+/// `$XDG_CONFIG_HOME`, or `~/.config` when it is unset as the XDG spec says
+fn xdg_config_home() -> Option<PathBuf> {
+    match std::env::var_os("XDG_CONFIG_HOME").filter(|dir| !dir.is_empty()) {
+        Some(dir) => Some(PathBuf::from(dir)),
+        None => std::env::var_os("HOME").map(|home| PathBuf::from(home).join(".config")),
+    }
+}
+
+/// Whether `path` names a settings file, with or without one of the extensions the
+/// config crate tries
+fn config_file_exists(path: &str) -> bool {
+    const EXTENSIONS: [&str; 6] = ["toml", "json", "yaml", "yml", "ini", "ron"];
+    Path::new(path).is_file() || EXTENSIONS.iter().any(|ext| Path::new(&format!("{}.{}", path, ext)).is_file())
+}
+
+/// Checks that the directories the settings write into exist, so a typo fails at
+/// startup with the path at fault rather than on the first opportunity. The ABIs
+/// are compiled into the binary, so there are none to look for at runtime.
+fn check_paths(settings: &Settings) -> Result<(), Vec<String>> {
+    let mut problems = Vec::new();
+
+    let files = [
+        ("output_csv", &settings.output_csv),
+        ("database_path", &settings.database_path),
+        ("paper_ledger_path", &settings.paper_ledger_path),
+        ("record_rpc", &settings.record_rpc),
+    ];
+    for (name, path) in files {
+        let Some(path) = path else { continue };
+        match Path::new(path).parent() {
+            Some(dir) if !dir.as_os_str().is_empty() && !dir.is_dir() => problems.push(format!(
+                "`{}` is {} but the directory {} doesn't exist; create it or change the path",
+                name,
+                path,
+                dir.display()
+            )),
+            _ => {}
+        }
+    }
+    if let Some(dir) = &settings.output_dir {
+        if Path::new(dir).exists() && !Path::new(dir).is_dir() {
+            problems.push(format!("`output_dir` is {} but that is a file, not a directory", dir));
+        }
+    }
+
+    if problems.is_empty() {
+        Ok(())
+    } else {
+        Err(problems)
+    }
+}
+
+/// Checks settings for values that would only fail later or produce nonsense,
+/// collecting every problem so they can all be fixed in one go
+fn validate(settings: &Settings) -> Result<(), Vec<String>> {
+    let mut problems = Vec::new();
+
+    if settings.check_interval_seconds == 0 {
+        problems.push("`check_interval_seconds` must be greater than 0".to_string());
+    }
+    if settings.interval_jitter_seconds.is_some_and(|jitter| !(0.0..).contains(&jitter)) {
+        problems.push("`interval_jitter_seconds` must not be negative".to_string());
+    }
+    if settings.staleness_threshold_seconds == Some(0) {
+        problems.push("`staleness_threshold_seconds` must be greater than 0".to_string());
+    }
+    if settings.output_csv.is_some() && settings.output_dir.is_some() {
+        problems.push("set either `output_csv` or `output_dir`, not both".to_string());
+    }
+    for kind in settings.sinks.iter().flatten() {
+        let configured = match kind {
+            sink::SinkKind::Csv => settings.output_csv.is_some() || settings.output_dir.is_some(),
+            sink::SinkKind::Sqlite => settings.database_path.is_some(),
+        };
+        if !configured {
+            let needs = match kind {
+                sink::SinkKind::Csv => "`output_csv` or `output_dir`",
+                sink::SinkKind::Sqlite => "`database_path`",
+            };
+            problems.push(format!("the {:?} sink is listed in `sinks` but {} isn't set", kind, needs));
+        }
+    }
+    if settings.spread_stream && settings.output_format == output::OutputFormat::Json {
+        problems.push("`spread_stream` and `output_format = \"json\"` both write to stdout, pick one".to_string());
+    }
+    if let Some(timeout) = settings.watchdog_timeout_seconds {
+        if timeout <= settings.check_interval_seconds {
+            problems.push(format!(
+                "`watchdog_timeout_seconds` ({}) must be longer than `check_interval_seconds` ({})",
+                timeout, settings.check_interval_seconds
+            ));
+        }
+    }
+    if settings.simulate_swap && settings.simulate_swap_from.is_none() {
+        problems.push("`simulate_swap` needs `simulate_swap_from`, an account holding the pair tokens".to_string());
+    }
+    if settings.max_rpc_requests_per_second == Some(0) {
+        problems.push("`max_rpc_requests_per_second` must be at least 1".to_string());
+    }
+    if settings.max_gas_price_gwei.is_some_and(|gwei| gwei <= 0.0) {
+        problems.push("`max_gas_price_gwei` must be greater than 0".to_string());
+    }
+    if settings.congestion_factor.is_some_and(|factor| factor <= 0.0) {
+        problems.push("`congestion_factor` must be greater than 0".to_string());
+    }
+    if !(settings.congestion_threshold > 0.0 && settings.congestion_threshold <= 1.0) {
+        problems.push(format!("`congestion_threshold` must be in (0, 1], got {}", settings.congestion_threshold));
+    }
+    let reference_deviation = settings.reference_price_source.as_ref().and_then(|source| source.max_deviation_pct);
+    if reference_deviation.is_some_and(|max| max <= 0.0) {
+        problems.push("`reference_price_source.max_deviation_pct` must be greater than 0".to_string());
+    }
+    if settings.confirmations == Some(0) {
+        problems.push("`confirmations` must be at least 1".to_string());
+    }
+    if settings.confirmations.is_some() && settings.price_confirmations > 0 {
+        problems.push("`price_confirmations` and `confirmations` can't be combined, pick one".to_string());
+    }
+    // A single check would hold everything it found and exit before any of it matured
+    if settings.confirmations.is_some() && settings.run_once && settings.at_block.is_none() {
+        problems.push("`confirmations` needs more than one check, it can't be combined with `run_once`/`--once`".to_string());
+    }
+    if settings.max_pool_idle_seconds == Some(0) {
+        problems.push("`max_pool_idle_seconds` must be greater than 0".to_string());
+    }
+    if settings.spread_window == Some(0) {
+        problems.push("`spread_window` must be at least 1".to_string());
+    }
+    if settings.max_concurrency == 0 {
+        problems.push("`max_concurrency` must be at least 1".to_string());
+    }
+    if settings.minimum_profit_percent.is_some() && settings.minimum_profit_bps.is_some() {
+        problems
+            .push("`minimum_profit_percent` and `minimum_profit_bps` are the same threshold, set only one".to_string());
+    }
+    if settings.provider_pool_size == 0 {
+        problems.push("`provider_pool_size` must be at least 1".to_string());
+    }
+    if settings.summary_every_n_ticks == Some(0) {
+        problems.push("`summary_every_n_ticks` must be greater than 0".to_string());
+    }
+    for tax in settings.token_taxes.iter().filter(|tax| tax.tax_bps >= 10_000) {
+        problems.push(format!("`token_taxes` for {:?} must be below 10000 bps, got {}", tax.token, tax.tax_bps));
+    }
+    if let Some(tiers) = settings.tiers {
+        if tiers.notable < 0.0 || tiers.strong < tiers.notable {
+            problems.push(format!(
+                "`tiers` needs 0 <= notable <= strong, got notable = {} and strong = {}",
+                tiers.notable, tiers.strong
+            ));
+        }
+    }
+    if settings.top_near_misses == Some(0) {
+        problems.push("`top_near_misses` must be greater than 0".to_string());
+    }
+    match (&settings.amount_in, settings.amount_in_usd) {
+        (Some(_), Some(_)) => problems.push("set only one of `amount_in` and `amount_in_usd`".to_string()),
+        (None, None) => problems.push("one of `amount_in` and `amount_in_usd` must be set".to_string()),
+        (Some(AmountIn::Each(amounts)), _) if amounts.is_empty() => {
+            problems.push("`amount_in` must list at least one amount".to_string())
+        }
+        (Some(AmountIn::Each(_)), _) if settings.amount_sweep.is_some() => {
+            problems.push("`amount_in` can't be a list when `amount_sweep` is set".to_string())
+        }
+        (Some(amount_in), _) => {
+            for amount in amount_in.amounts().into_iter().filter(|amount| *amount <= 0.0) {
+                problems.push(format!("`amount_in` must be greater than 0, got {}", amount));
+            }
+        }
+        (None, Some(usd)) if usd <= 0.0 => {
+            problems.push(format!("`amount_in_usd` must be greater than 0, got {}", usd))
+        }
+        (None, Some(_)) if settings.amount_sweep.is_some() => {
+            problems.push("`amount_in_usd` can't be combined with `amount_sweep`, which is in Token A".to_string())
+        }
+        (None, Some(_)) => {}
+    }
+    if let Some(sweep) = settings.amount_sweep {
+        if sweep.min <= 0.0 || sweep.max < sweep.min {
+            problems.push(format!("`amount_sweep` needs 0 < min <= max, got {} to {}", sweep.min, sweep.max));
+        }
+        if sweep.steps == 0 {
+            problems.push("`amount_sweep.steps` must be at least 1".to_string());
+        }
+    }
+    if settings.minimum_profit_threshold < 0.0 {
+        problems.push(format!(
+            "`minimum_profit_threshold` must not be negative, got {}",
+            settings.minimum_profit_threshold
+        ));
+    }
+
+    let dexes = settings.enabled_dexes();
+    if dexes.len() < 2 {
+        problems.push("at least two enabled DEXes must be configured".to_string());
+    }
+    for (i, dex) in dexes.iter().enumerate() {
+        if let Some(other) = dexes[..i].iter().find(|d| d.router_address == dex.router_address) {
+            problems.push(format!(
+                "DEXes {} and {} share the router address {:?}",
+                other.name, dex.name, dex.router_address
+            ));
+        }
+    }
+
+    for dex in &settings.dexes {
+        if dex.kind == DexKind::V3 && dex.fee_tier.is_none() {
+            problems.push(format!("V3 DEX {} needs a `fee_tier`", dex.name));
+        }
+    }
+
+    if settings.pairs.is_empty() {
+        problems.push("at least one token pair must be configured".to_string());
+    }
+    for pair in &settings.pairs {
+        if pair.token_a == pair.token_b {
+            problems.push(format!("pair {} uses {:?} as both tokens", pair.label, pair.token_a));
+        }
+        if pair.profit_in == PairToken::A && settings.profit_currency.is_some() {
+            problems.push(format!("pair {} sets `profit_in = \"a\"`, which conflicts with `profit_currency`", pair.label));
+        }
+        if let Some(threshold) = pair.minimum_profit_threshold.filter(|&threshold| threshold < 0.0) {
+            problems.push(format!("pair {} has a negative `minimum_profit_threshold`, got {}", pair.label, threshold));
+        }
+    }
+
+    if settings.triangular && settings.cycles.is_empty() {
+        problems.push("`triangular` is enabled but no cycles are configured".to_string());
+    }
+    for cycle in &settings.cycles {
+        if cycle.tokens.len() < 3 {
+            problems.push(format!("cycle {} needs at least three tokens", cycle.label));
+        }
+    }
+
+    if problems.is_empty() {
+        Ok(())
+    } else {
+        Err(problems)
+    }
+}
+
+/// The outcome of pricing one pair for a tick
+struct PairScan {
+    trade_size: f64,
+    /// The best spread between two DEXes, as a percentage of the buy price
+    spread_pct: f64,
+    opportunity: Option<Opportunity>,
+    /// The smallest spread that would still have cleared the thresholds, given an opportunity
+    spread_needed_pct: Option<f64>,
+    /// How far the trade fell short of the thresholds, if that is all that stopped it
+    near_miss: Option<stats::NearMiss>,
+}
+
+impl PairScan {
+    /// A scan that found no opportunity, for a reason other than the thresholds
+    fn skipped(trade_size: f64, spread_pct: f64) -> Self {
+        PairScan {
+            trade_size,
+            spread_pct,
+            opportunity: None,
+            spread_needed_pct: None,
+            near_miss: None,
+        }
+    }
+}
+
+/// Lists how a pair's spread and profit moved across the configured trade sizes
+fn log_trade_sizes(pair: &Pair, scans: &[PairScan], decimals: usize) {
+    info!("[{}] By trade size:", pair.label);
+    for scan in scans {
+        match &scan.opportunity {
+            Some(opportunity) => info!(
+                "  - {} {}: spread {:.3}%, net profit {:.*} {} ({:.4}%)",
+                scan.trade_size,
+                pair.symbol_a(),
+                scan.spread_pct,
+                decimals,
+                opportunity.net_profit,
+                pair.symbol_b(),
+                opportunity.profit_percent
+            ),
+            None => info!("  - {} {}: spread {:.3}%, no opportunity", scan.trade_size, pair.symbol_a(), scan.spread_pct),
+        }
+    }
+}
+
+/// Everything needed to price pairs on the active endpoint
+struct Scanner<'a, M> {
+    settings: &'a Settings,
+    markets: &'a registry::MarketRegistry<M>,
+    block_number: Option<u64>,
+    /// Set for `--at-block`, to quote at that block rather than the latest one
+    at_block: Option<BlockId>,
+    /// The Unix time of the `at_block` block, when pools' idle times are checked
+    block_timestamp: Option<u64>,
+    network: Network,
+    retry: RetryPolicy,
+    sinks: &'a [Box<dyn sink::Sink>],
+    metrics: &'a metrics::Metrics,
+    /// Set for `--benchmark`, which times every quote
+    benchmark: Option<&'a benchmark::Benchmark>,
+    freshness: Option<&'a freshness::Freshness>,
+    clock: &'a dyn Clock,
+    reference_prices: Option<&'a reference::ReferencePrices>,
+}
+
+impl<M: Middleware + 'static> Scanner<'_, M> {
+    /// Scans a pair at every configured trade size. A sweep keeps only the most profitable
+    /// size, while a list of `amount_in` values keeps each one in the listed order.
+    async fn scan_pair(&self, pair: &Pair) -> Result<Vec<PairScan>> {
+        let &Scanner { markets, settings, retry, at_block, .. } = self;
+        let from_usd = amount_in_from_usd(markets, settings, pair.token_a, pair.decimals_a, retry, at_block).await?;
+        let trade_sizes = match from_usd {
+            Some(amount_in) => {
+                debug!("[{}] `amount_in_usd` buys {} {}", pair.label, amount_in, pair.symbol_a());
+                vec![amount_in]
+            }
+            None => settings.trade_sizes(),
+        };
+        // A size that can't be priced, e.g. one too large for a thin pool, leaves the others
+        // to be reported. Only when every size fails is the pair's scan an error.
+        let several_sizes = trade_sizes.len() > 1;
+        let mut scans = Vec::with_capacity(trade_sizes.len());
+        let mut first_error = None;
+        for amount_in in trade_sizes {
+            match self.scan_pair_at(pair, amount_in).await {
+                Ok(scan) => scans.push(scan),
+                Err(e) if several_sizes => {
+                    warn!(pair = %pair.label, "Error scanning {} {}: {}", amount_in, pair.symbol_a(), e);
+                    first_error.get_or_insert(e);
+                }
+                Err(e) => return Err(e),
+            }
+        }
+        if scans.is_empty() {
+            return Err(first_error.expect("a size that wasn't scanned failed"));
+        }
+        if settings.reports_each_size() {
+            return Ok(scans);
+        }
+
+        let mut best: Option<PairScan> = None;
+        for scan in scans {
+            let profit = |scan: &PairScan| scan.opportunity.as_ref().map(|opportunity| opportunity.net_profit);
+            if best.as_ref().is_none_or(|best| profit(&scan) > profit(best)) {
+                best = Some(scan);
+            }
+        }
+        Ok(vec![best.expect("at least one trade size was scanned")])
+    }
+
+    /// Prices one pair on every DEX and checks the best buy/sell combination for an opportunity
+    async fn scan_pair_at(&self, pair: &Pair, trade_size: f64) -> Result<PairScan> {
+        let &Scanner {
+            settings,
+            markets,
+            block_number,
+            at_block,
+            block_timestamp,
+            network,
+            retry,
+            sinks,
+            metrics,
+            benchmark,
+            freshness,
+            clock,
+            reference_prices,
+        } = self;
+        let contracts = markets.contracts();
+
+        // Simulate `trade_size` Token A, plus a tiny amount to measure price impact
+        let amount_in = to_units(trade_size, pair.decimals_a)?;
+        let reference_units = to_units(trade_size * PRICE_IMPACT_REFERENCE_FRACTION, pair.decimals_a)?.max(U256::one());
+        let reference_in = from_units(reference_units, pair.decimals_a)?;
+
+        // 3. MULTI-DEX PRICE FETCHING [cite: 12]
+        // Quotes are fetched concurrently; `join_all` keeps results in config order
+        let fetched_at = Utc::now();
+        let results = join_all(
+            contracts
+                .iter()
+                .map(|(dex, contract)| async move {
+                    // Timed on its own, so slow endpoints and DEXes stand out when tuning timeouts
+                    let timed_price = async {
+                        let started = clock.now();
+                        let price = get_price(contract.as_ref(), amount_in, pair, retry, at_block).await;
+                        let took = clock.now() - started;
+                        metrics.record_fetch_duration(&dex.name, &pair.label, took);
+                        if let Some(benchmark) = benchmark {
+                            benchmark.record_fetch(took, price.is_ok());
+                        }
+                        debug!("[{}] Quote from {} took {}ms", pair.label, dex.name, took.as_millis());
+                        price
+                    };
+                    let (price, reference) = futures::try_join!(
+                        timed_price,
+                        get_price(contract.as_ref(), reference_units, pair, retry, at_block),
+                    )?;
+                    Ok::<_, BotError>((price, price_impact_pct(trade_size, price, reference_in, reference)))
+                }),
+        )
+        .await;
+
+        let mut prices = Vec::with_capacity(contracts.len());
+        let mut impacts = Vec::with_capacity(contracts.len());
+        let mut first_error = None;
+        let mut excluded = Vec::new();
+        for ((dex, _), result) in contracts.iter().zip(results) {
+            metrics.record_price(&dex.name, &pair.label, result.as_ref().ok().map(|(price, _)| *price));
+            match result {
+                Ok((price, impact)) => {
+                    if let Some(freshness) = freshness {
+                        freshness.record_fetch(&dex.name, &pair.label, clock.now());
+                    }
+                    let row = db::PriceRow {
+                        timestamp: fetched_at,
+                        block_number,
+                        dex_name: dex.name.clone(),
+                        pair: pair.label.clone(),
+                        amount_in: trade_size,
+                        amount_out: price,
+                    };
+                    sink::record_price(sinks, &row).await;
+                    prices.push((dex, price));
+                    impacts.push(impact);
+                }
+                Err(e) => {
+                    warn!(pair = %pair.label, dex = %dex.name, "Error fetching price: {}", e);
+                    excluded.push(dex.name.as_str());
+                    first_error.get_or_insert(e);
+                }
+            }
+        }
+
+        // Comparing a fresh price against a DEX that stopped answering could show a false opportunity
+        if let Some(freshness) = freshness {
+            let stale = freshness.stale(&pair.label, clock.now());
+            if !stale.is_empty() {
+                return Err(eyre::eyre!(
+                    "No fresh price from {} in {}s, skipping detection",
+                    stale.join(", "),
+                    freshness.max_age().as_secs()
+                ));
+            }
+        }
+
+        // The DEXes that did answer are still compared, as long as there are two of them.
+        // Keep the first failure as the cause so it can be told apart from e.g. a revert.
+        if let Some(e) = first_error {
+            if prices.len() < 2 {
+                let failed = contracts.len() - prices.len();
+                return Err(eyre::Report::new(e).wrap_err(format!("{} of {} DEX quotes failed", failed, contracts.len())));
+            }
+            warn!(
+                pair = %pair.label,
+                "Comparing {} of {} DEXes, excluded this tick: {}",
+                prices.len(),
+                contracts.len(),
+                excluded.join(", ")
+            );
+        }
+
+        for ((dex, price), impact) in prices.iter().zip(&impacts) {
+            debug!(
+                "[{}] Price on {}: {} {} -> {:.*} {} (price impact {:.2}%)",
+                pair.label,
+                dex.name,
+                trade_size,
+                pair.symbol_a(),
+                settings.display_decimals,
+                price,
+                pair.symbol_b(),
+                impact
+            );
+        }
+
+        // Off-chain prices catch a dislocation even when every DEX is off by the same amount
+        let mut deviations = HashMap::new();
+        if let Some(reference_prices) = reference_prices {
+            match reference_prices.price(pair.token_a, pair.token_b, clock.now()).await {
+                Ok(unit_price) => {
+                    let reference = unit_price * trade_size;
+                    let mut summary = Vec::with_capacity(prices.len());
+                    for (dex, price) in &prices {
+                        let deviation = reference::deviation_pct(*price, reference);
+                        summary.push(format!("{} {:+.2}%", dex.name, deviation));
+                        if let Some(max) = reference_prices.max_deviation_pct().filter(|&max| deviation.abs() > max) {
+                            warn!(
+                                pair = %pair.label,
+                                "{} is {:+.2}% from the reference price, beyond the {:.2}% maximum",
+                                dex.name, deviation, max
+                            );
+                        }
+                        deviations.insert(dex.name.as_str(), deviation);
+                    }
+                    info!(
+                        "[{}] Reference price {:.*} {}: {}",
+                        pair.label,
+                        settings.display_decimals,
+                        reference,
+                        pair.symbol_b(),
+                        summary.join(", ")
+                    );
+                }
+                Err(e) => warn!(pair = %pair.label, "Error fetching reference price: {}", e),
+            }
+        }
+
+        // 4. ARBITRAGE OPPORTUNITY DETECTION [cite: 13] & PROFIT CALCULATION [cite: 14]
+        // Gas is the same whichever DEXes trade, so the quote-only ranking can leave it out
+        let route_tax_bps = settings.route_tax_bps([&pair.path(false), &pair.path(true)]);
+        let ranking = rank_opportunities(pair, trade_size, &prices, Costs::new(None, settings).taxed(route_tax_bps));
+        if prices.len() > 2 {
+            log_ranking(settings, pair, &ranking);
+        }
+        let best = ranking.first().ok_or_else(|| eyre::eyre!("At least two DEX prices are needed"))?;
+        let priced_dex = |name: &str| {
+            prices.iter().map(|&(dex, _)| dex).find(|dex| dex.name == name).expect("every ranked DEX was priced")
+        };
+        let (buy_dex, sell_dex) = (priced_dex(&best.buy_dex), priced_dex(&best.sell_dex));
+        let spread = best.sell_price - best.buy_price;
+        let contract_for = |dex: &Dex| markets.quoter(dex.router_address).expect("every priced DEX has a contract");
+        let price_of = |dex: &Dex| {
+            prices
+                .iter()
+                .find(|(d, _)| std::ptr::eq(*d, dex))
+                .map(|(_, price)| *price)
+                .expect("every compared DEX was priced this tick")
+        };
+        let impact_of = |dex: &Dex| {
+            prices
+                .iter()
+                .zip(&impacts)
+                .find(|((d, _), _)| std::ptr::eq(*d, dex))
+                .map(|(_, impact)| *impact)
+                .expect("every compared DEX was priced this tick")
+        };
+        let amount_b_in = price_of(buy_dex);
+        let spread_pct = spread / amount_b_in * 100.0;
+        if settings.spread_stream {
+            // One column per configured DEX, left empty when it couldn't be priced
+            let dex_prices: Vec<Option<f64>> = contracts
+                .iter()
+                .map(|(dex, _)| prices.iter().find(|(d, _)| std::ptr::eq(*d, dex)).map(|(_, price)| *price))
+                .collect();
+            println!(
+                "{}",
+                output::spread_line(fetched_at, &pair.label, &dex_prices, spread_pct, settings.display_decimals)
+            );
+        }
+
+        // However good its quotes look, a pool this thin or this quiet can't be traded against
+        if settings.min_liquidity_usdc.is_some() || settings.max_pool_idle_seconds.is_some() {
+            // Reserves are valued in USDC, so one floor fits pairs of any Token B
+            let token_b_usdc = match settings.min_liquidity_usdc {
+                Some(_) => get_price_usdc(markets, settings, pair.token_b, pair.decimals_b, retry, at_block)
+                    .await
+                    .map_err(|e| eyre::eyre!("Error pricing {} for `min_liquidity_usdc`: {}", pair.symbol_b(), e))?,
+                None => 0.0,
+            };
+            for dex in [buy_dex, sell_dex].into_iter().filter(|dex| dex.kind == DexKind::V2) {
+                let pool = pool_state(dex, markets.client().clone(), pair, retry, at_block)
+                    .await
+                    .map_err(|e| eyre::eyre!("Error reading {} pool reserves: {}", dex.name, e))?;
+                let liquidity_usdc = pool.reserve_b * token_b_usdc;
+                if let Some(min_liquidity) = settings.min_liquidity_usdc.filter(|&min| liquidity_usdc < min) {
+                    debug!(
+                        "[{}] Skipping detection, the {} pool holds {:.2} {} (${:.2}), below the ${:.2} minimum",
+                        pair.label, dex.name, pool.reserve_b, pair.symbol_b(), liquidity_usdc, min_liquidity
+                    );
+                    return Ok(PairScan::skipped(trade_size, spread_pct));
+                }
+                let idle_seconds = pool.idle_seconds(quoted_at(at_block, block_timestamp, clock)?);
+                if let Some(max_idle) = settings.max_pool_idle_seconds.filter(|&max| idle_seconds > max) {
+                    info!(
+                        "[{}] Skipping detection, the {} pool last traded {}s ago, over the {}s limit",
+                        pair.label, dex.name, idle_seconds, max_idle
+                    );
+                    return Ok(PairScan::skipped(trade_size, spread_pct));
+                }
+            }
+        }
+
+        // Round trip: spend the Token B that `amount_in` Token A costs on the buy DEX,
+        // then sell the Token A actually received on the sell DEX
+        let amount_b_out = round_trip(contract_for(buy_dex), contract_for(sell_dex), amount_b_in, pair, retry, at_block)
+            .await
+            .map_err(|e| eyre::eyre!("Error simulating round trip {} -> {}: {}", buy_dex.name, sell_dex.name, e))?;
+
+        // 5. GAS COST ESTIMATION from the live gas price, priced in Token B via the first DEX
+        let (token_b, decimals_b) = (pair.token_b, pair.decimals_b);
+        let gas_contract = contracts[0].1.as_ref();
+        let gas = match gas_cost_in(token_b, decimals_b, gas_contract, settings, network, retry, at_block).await {
+            Ok(estimate) => Some(estimate),
+            Err(e) => {
+                warn!(pair = %pair.label, "Error estimating gas cost, using simulated value: {}", e);
+                None
+            }
+        };
+
+        let buy = (buy_dex, amount_b_in);
+        let sell = (sell_dex, price_of(sell_dex));
+        let costs = Costs::new(gas, settings).taxed(route_tax_bps);
+
+        // With a profit currency, or profits in Token A, the threshold is in that token, so
+        // convert it into Token B
+        let mut thresholds = settings.thresholds_for(pair);
+        let currency_rate = match (&settings.profit_currency, pair.profit_in) {
+            (Some(currency), _) if currency.token != token_b => {
+                let decimals = markets.decimals(currency.token).unwrap_or(currency.decimals);
+                let one_b = parse_units(1, decimals_b)?.into();
+                let path = vec![token_b, currency.token];
+                let rate = quote_at(contracts[0].1.as_ref(), one_b, path, decimals, retry, at_block)
+                    .await
+                    .map_err(|e| eyre::eyre!("Error converting profit into {}: {}", currency.label, e))?;
+                thresholds.minimum_profit /= rate;
+                Some(rate)
+            }
+            (Some(_), _) => Some(1.0),
+            (None, PairToken::A) => {
+                let one_b = parse_units(1, decimals_b)?.into();
+                let rate = quote_at(contracts[0].1.as_ref(), one_b, pair.path(true), pair.decimals_a, retry, at_block)
+                    .await
+                    .map_err(|e| eyre::eyre!("Error converting profit into {}: {}", pair.symbol_a(), e))?;
+                thresholds.minimum_profit /= rate;
+                Some(rate)
+            }
+            (None, PairToken::B) => None,
+        };
+
+        let mut round_trip_out = amount_b_out;
+        let mut opportunity = check_opportunity(pair, trade_size, buy, sell, round_trip_out, costs, thresholds);
+
+        // Quotes can't see reverts or transfer taxes, so optionally run the real swaps too
+        if opportunity.is_some() && settings.simulate_swap {
+            let from = settings.simulate_swap_from.expect("validated: simulate_swap has an account");
+            // Run at the quotes' block, so the simulation confirms the prices that were seen
+            let swap = SwapCall { from, block: at_block, retry, clock };
+            let simulated_out = match simulate_round_trip(markets, (buy_dex, sell_dex), amount_b_in, pair, swap).await {
+                Ok(simulated_out) => simulated_out,
+                Err(e) => {
+                    warn!(pair = %pair.label, "Ignoring opportunity, simulated swap failed: {}", e);
+                    return Ok(PairScan::skipped(trade_size, spread_pct));
+                }
+            };
+            if simulated_out < amount_b_out {
+                warn!(
+                    pair = %pair.label,
+                    "Simulated swaps return {:.*}, less than the quoted {:.*} (transfer tax?)",
+                    settings.display_decimals, simulated_out, settings.display_decimals, amount_b_out
+                );
+            }
+            round_trip_out = simulated_out;
+            opportunity = check_opportunity(pair, trade_size, buy, sell, round_trip_out, costs, thresholds);
+        }
+        let Some(mut opportunity) = opportunity else {
+            // Kept for the summary, to show how close the thresholds came to letting it through
+            let trade = price_trade(pair, trade_size, buy, sell, round_trip_out, costs);
+            let near_miss = stats::NearMiss {
+                pair: pair.label.clone(),
+                buy_dex: buy_dex.name.clone(),
+                sell_dex: sell_dex.name.clone(),
+                trade_size,
+                spread_pct,
+                net_profit: trade.net_profit,
+                shortfall: thresholds.shortfall(trade.net_profit, trade.buy_price),
+                symbol: pair.symbol_b().to_string(),
+            };
+            return Ok(PairScan { near_miss: Some(near_miss), ..PairScan::skipped(trade_size, spread_pct) });
+        };
+        opportunity.net_profit_in_currency = currency_rate.map(|rate| opportunity.net_profit * rate);
+
+        opportunity.block_number = block_number;
+        opportunity.buy_deviation_pct = deviations.get(buy_dex.name.as_str()).copied();
+        opportunity.sell_deviation_pct = deviations.get(sell_dex.name.as_str()).copied();
+        // A thin pool can quote a spread no real trade of this size could capture
+        opportunity.price_impact_pct = impact_of(buy_dex).max(impact_of(sell_dex));
+        if let Some(max_impact) = settings.max_price_impact_pct {
+            if opportunity.price_impact_pct > max_impact {
+                warn!(
+                    pair = %pair.label,
+                    "Ignoring opportunity with {:.2}% price impact, above the {:.2}% maximum",
+                    opportunity.price_impact_pct, max_impact
+                );
+                return Ok(PairScan::skipped(trade_size, spread_pct));
+            }
+        }
+        // How far the spread could narrow with the opportunity still clearing the thresholds,
+        // since spread and profit are both relative to the buy price
+        let mut headroom_pct = (opportunity.net_profit - thresholds.minimum_profit) / opportunity.buy_price * 100.0;
+        if let Some(minimum_percent) = thresholds.minimum_profit_percent {
+            headroom_pct = headroom_pct.min(opportunity.profit_percent - minimum_percent);
+        }
+        Ok(PairScan {
+            trade_size,
+            spread_pct,
+            opportunity: Some(opportunity),
+            spread_needed_pct: Some(spread_pct - headroom_pct),
+            near_miss: None,
+        })
+    }
+}
+
+/// How to simulate a swap: from which account, at which block and with which retries
+#[derive(Clone, Copy)]
+struct SwapCall<'a> {
+    from: Address,
+    block: Option<BlockId>,
+    retry: RetryPolicy,
+    /// Sets the swap deadline
+    clock: &'a dyn Clock,
+}
+
+/// Runs both legs of a round trip as `swapExactTokensForTokens` eth_calls from `swap.from`,
+/// which must hold and have approved both tokens, and returns the Token B received
+async fn simulate_round_trip<M: Middleware + 'static>(
+    markets: &registry::MarketRegistry<M>,
+    (buy_dex, sell_dex): (&Dex, &Dex),
+    amount_b_in: f64,
+    pair: &Pair,
+    swap: SwapCall<'_>,
+) -> Result<f64> {
+    let client = markets.client().clone();
+    let amount_b_in = to_units(amount_b_in, pair.decimals_b)?;
+    let amount_a = simulate_swap(buy_dex, client.clone(), amount_b_in, pair.path(true), swap)
+        .await
+        .map_err(|e| eyre::eyre!("buying on {}: {}", buy_dex.name, e))?;
+    let amount_b = simulate_swap(sell_dex, client, amount_a, pair.path(false), swap)
+        .await
+        .map_err(|e| eyre::eyre!("selling on {}: {}", sell_dex.name, e))?;
+    Ok(from_units(amount_b, pair.decimals_b)?)
+}
+
+/// What a V2 pool's `getReserves` says about how tradable it is
+struct PoolState {
+    reserve_b: f64,
+    /// Unix time of the block that last changed the reserves, i.e. the last swap, mint or burn
+    last_active: i64,
+}
+
+impl PoolState {
+    /// Seconds since the pool last traded, as of Unix time `now`
+    fn idle_seconds(&self, now: u64) -> u64 {
+        (now as i64).saturating_sub(self.last_active).max(0) as u64
+    }
+}
+
+/// The state of the V2 pool `dex` pays Token B out of for this pair, which for pairs
+/// with intermediate tokens is the pool of the last hop. Read at `block`, like the quotes.
+async fn pool_state<M: Middleware + 'static>(
+    dex: &Dex,
+    client: Arc<M>,
+    pair: &Pair,
+    retry: RetryPolicy,
+    block: Option<BlockId>,
+) -> Result<PoolState> {
+    let path = pair.path(false);
+    let token_in = path[path.len() - 2];
+    let router = IUniswapV2Router02::new(dex.router_address, client.clone());
+    let factory = IUniswapV2Factory::new(call_with_retry(router.factory(), retry, block).await?, client.clone());
+    let pool = call_with_retry(factory.get_pair(token_in, pair.token_b), retry, block).await?;
+    if pool.is_zero() {
+        return Err(eyre::eyre!("no pool for {:?} -> {:?}", token_in, pair.token_b));
+    }
+    let pool = IUniswapV2Pair::new(pool, client);
+    let token_0 = call_with_retry(pool.token_0(), retry, block).await?;
+    let (reserve_0, reserve_1, block_timestamp_last) = call_with_retry(pool.get_reserves(), retry, block).await?;
+    let reserve = if token_0 == pair.token_b { reserve_0 } else { reserve_1 };
+    Ok(PoolState {
+        reserve_b: from_units(U256::from(reserve), pair.decimals_b)?,
+        last_active: block_timestamp_last.into(),
+    })
+}
+
+/// Calls `swapExactTokensForTokens` without broadcasting and returns the amount received,
+/// or the revert reason if the swap would fail
+async fn simulate_swap<M: Middleware + 'static>(
+    dex: &Dex,
+    client: Arc<M>,
+    amount_in: U256,
+    path: Vec<Address>,
+    swap: SwapCall<'_>,
+) -> Result<U256> {
+    if dex.kind != DexKind::V2 {
+        return Err(eyre::eyre!("only V2 routers can be simulated"));
+    }
+    let router = IUniswapV2Router02::new(dex.router_address, client);
+    let deadline = U256::from(swap.clock.unix_seconds() + 300);
+    let call = router.swap_exact_tokens_for_tokens(amount_in, U256::zero(), path, swap.from, deadline).from(swap.from);
+    // Reverts come back as `BotError::Reverted` with their reason, and aren't retried
+    let amounts = call_with_retry(call, swap.retry, swap.block).await?;
+    Ok(final_amount(&amounts)?)
+}
+
+/// Quotes each `amount_in` of a cycle's first token around the whole cycle on one DEX and
+/// checks whether more of it comes back than went in, after fees, slippage and gas
+async fn check_triangular<M: Middleware + 'static>(
+    settings: &Settings,
+    dex: &Dex,
+    cycle: &Cycle,
+    markets: &registry::MarketRegistry<M>,
+    block: Option<BlockId>,
+    network: Network,
+    retry: RetryPolicy,
+) -> Result<Vec<Opportunity>> {
+    let contract = markets.quoter(dex.router_address).expect("every DEX has a contract");
+    let amounts = match amount_in_from_usd(markets, settings, cycle.tokens[0], cycle.decimals, retry, block).await? {
+        Some(amount) => vec![amount],
+        None => settings.amounts_in(),
+    };
+    let mut quotes = Vec::new();
+    for amount in amounts {
+        let amount_in = to_units(amount, cycle.decimals)?;
+        let amount_out = quote_at(contract, amount_in, cycle.path(), cycle.decimals, retry, block).await?;
+        debug!(
+            "[{}] Cycle on {}: {} {} -> {:.*} {}",
+            cycle.label, dex.name, amount, cycle.symbol(), settings.display_decimals, amount_out, cycle.symbol()
+        );
+        quotes.push((amount, amount_out));
+    }
+
+    let gas = match gas_cost_in(cycle.tokens[0], cycle.decimals, contract, settings, network, retry, block).await {
+        Ok(estimate) => Some(estimate),
+        Err(e) => {
+            warn!(cycle = %cycle.label, "Error estimating gas cost, using simulated value: {}", e);
+            None
+        }
+    };
+
+    let costs = Costs::new(gas, settings).taxed(settings.route_tax_bps([&cycle.path()]));
+    Ok(quotes
+        .into_iter()
+        .filter_map(|(amount_in, amount_out)| check_cycle(cycle, dex, amount_in, amount_out, costs, settings.thresholds()))
+        .collect())
+}
+
+/// Computes the profit of a triangular trade and returns the opportunity if it clears
+/// the threshold. Amounts are in the cycle's first token.
+fn check_cycle(
+    cycle: &Cycle,
+    dex: &Dex,
+    amount_in: f64,
+    amount_out: f64,
+    costs: Costs,
+    thresholds: Thresholds,
+) -> Option<Opportunity> {
+    let gross_profit = amount_out - amount_in;
+    // One swap per token in the cycle, each roughly the size of the input
+    let fees = amount_in * bps(dex.fee_bps) * cycle.tokens.len() as f64;
+    let slippage = amount_out * bps(costs.slippage_bps);
+    let transfer_tax = amount_in * bps(costs.tax_bps);
+    let gas_cost = costs.gas;
+    let net_profit = gross_profit - fees - slippage - transfer_tax - gas_cost;
+    let profit_percent = net_profit / amount_in * 100.0;
+
+    thresholds.cleared_by(net_profit, profit_percent).then(|| Opportunity {
+        id: String::new(),
+        timestamp: Utc::now(),
+        block_number: None,
+        pair: cycle.label.clone(),
+        symbol_a: cycle.symbol().to_string(),
+        symbol_b: cycle.symbol().to_string(),
+        amount_in,
+        buy_dex: dex.name.clone(),
+        sell_dex: dex.name.clone(),
+        buy_price: amount_in,
+        sell_price: amount_out,
+        round_trip_out: amount_out,
+        gross_profit,
+        fees,
+        slippage,
+        transfer_tax,
+        gas_cost,
+        gas_estimate: costs.gas_estimate,
+        net_profit,
+        net_profit_in_currency: None,
+        profit_percent,
+        price_impact_pct: 0.0,
+        buy_deviation_pct: None,
+        sell_deviation_pct: None,
+        confirmations: None,
+        tier: tier::Tier::Info,
+    })
+}
+
+/// Quotes 1 WMATIC in USDC on the first configured V2 router, at `block` if given
+async fn get_matic_price_usdc<M: Middleware + 'static>(
+    markets: &registry::MarketRegistry<M>,
+    settings: &Settings,
+    retry: RetryPolicy,
+    block: Option<BlockId>,
+) -> Result<f64> {
+    get_price_usdc(markets, settings, settings.wmatic, MATIC_DECIMALS, retry, block).await
+}
+
+/// Fetches the price of one `token` in USDC from the first V2 router, at `block` if given
+async fn get_price_usdc<M: Middleware + 'static>(
+    markets: &registry::MarketRegistry<M>,
+    settings: &Settings,
+    token: Address,
+    decimals: u32,
+    retry: RetryPolicy,
+    block: Option<BlockId>,
+) -> Result<f64> {
+    if token == settings.usdc {
+        return Ok(1.0);
+    }
+    let (_, contract) = markets
+        .contracts()
+        .iter()
+        .find(|(dex, _)| dex.kind == DexKind::V2)
+        .ok_or_else(|| eyre::eyre!("No V2 router is configured"))?;
+    let one_token = parse_units(1, decimals)?.into();
+    let usdc_decimals = markets.decimals(settings.usdc).unwrap_or(USDC_DECIMALS);
+    Ok(quote_at(contract.as_ref(), one_token, vec![token, settings.usdc], usdc_decimals, retry, block).await?)
+}
+
+/// How much `token` `amount_in_usd` buys at its USDC price as of `block` (the latest if
+/// unset), or `None` when trade sizes are set in the token itself
+async fn amount_in_from_usd<M: Middleware + 'static>(
+    markets: &registry::MarketRegistry<M>,
+    settings: &Settings,
+    token: Address,
+    decimals: u32,
+    retry: RetryPolicy,
+    block: Option<BlockId>,
+) -> Result<Option<f64>> {
+    let Some(usd) = settings.amount_in_usd else {
+        return Ok(None);
+    };
+    let price = get_price_usdc(markets, settings, token, decimals, retry, block)
+        .await
+        .map_err(|e| eyre::eyre!("Error pricing `amount_in_usd`: {}", e))?;
+    Ok(Some(usd / price))
+}
+
+/// Fetches how full the latest block is, as gasUsed / gasLimit, giving up after `timeout`
+async fn get_block_utilization<M: Middleware + 'static>(client: &M, timeout: Duration) -> Result<f64> {
+    let block = tokio::time::timeout(timeout, client.get_block(BlockNumber::Latest))
+        .await
+        .map_err(|_| BotError::Timeout(timeout))?
+        .map_err(|e| BotError::Rpc(e.to_string()))?
+        .ok_or_else(|| eyre::eyre!("the node returned no latest block"))?;
+    if block.gas_limit.is_zero() {
+        return Err(eyre::eyre!("the latest block has no gas limit"));
+    }
+    Ok(from_units(block.gas_used, 0)? / from_units(block.gas_limit, 0)?)
+}
+
+/// Fetches the Unix time of block `number`, giving up after `timeout`
+async fn get_block_timestamp<M: Middleware + 'static>(client: &M, number: u64, timeout: Duration) -> Result<u64> {
+    let block = tokio::time::timeout(timeout, client.get_block(number))
+        .await
+        .map_err(|_| BotError::Timeout(timeout))?
+        .map_err(|e| BotError::Rpc(e.to_string()))?
+        .ok_or_else(|| eyre::eyre!("the node doesn't have block {}", number))?;
+    Ok(block.timestamp.low_u64())
+}
+
+/// The time the quotes describe: the clock's for the latest block, or the block's own
+/// for a past one, which without its timestamp is unknown
+fn quoted_at(at_block: Option<BlockId>, block_timestamp: Option<u64>, clock: &dyn Clock) -> Result<u64> {
+    match (at_block, block_timestamp) {
+        (None, _) => Ok(clock.unix_seconds()),
+        (Some(_), Some(timestamp)) => Ok(timestamp),
+        (Some(_), None) => Err(eyre::eyre!("The quoted block's timestamp is unknown, so idle pools can't be told")),
+    }
+}
+
+/// Fetches the latest block's number and hash, giving up after `timeout`
+async fn get_latest_block<M: Middleware + 'static>(client: &M, timeout: Duration) -> Result<(u64, H256)> {
+    let block = tokio::time::timeout(timeout, client.get_block(BlockNumber::Latest))
+        .await
+        .map_err(|_| BotError::Timeout(timeout))?
+        .map_err(|e| BotError::Rpc(e.to_string()))?
+        .ok_or_else(|| eyre::eyre!("the node returned no latest block"))?;
+    match (block.number, block.hash) {
+        (Some(number), Some(hash)) => Ok((number.as_u64(), hash)),
+        _ => Err(eyre::eyre!("the latest block is still pending")),
+    }
+}
+
+/// Holds this tick's opportunities until their block is confirmed. Without the block's
+/// hash a reorg couldn't be detected, so they are dropped instead.
+fn hold_for_confirmations(confirmations: &mut confirm::Confirmations, detected: Vec<Opportunity>, block_hash: Option<H256>) {
+    for opportunity in detected {
+        match block_hash {
+            Some(hash) if opportunity.block_number.is_some() => confirmations.hold(opportunity, hash),
+            _ => warn!(pair = %opportunity.pair, "Dropping opportunity, its block is unknown so it can't be confirmed"),
+        }
+    }
+}
+
+/// The held opportunities confirmed as of block `latest`, tagged with their depth.
+/// Those whose block is no longer canonical were found in an orphaned block and are dropped.
+async fn confirmed<M: Middleware + 'static>(
+    confirmations: &mut confirm::Confirmations,
+    latest: u64,
+    client: &M,
+    timeout: Duration,
+) -> Vec<Opportunity> {
+    let mut confirmed = Vec::new();
+    for (hash, mut opportunity) in confirmations.matured(latest) {
+        let number = opportunity.block_number.expect("only opportunities with a block are held");
+        match confirm::block_hash(client, number, timeout).await {
+            Ok(Some(canonical)) if canonical == hash => {
+                opportunity.confirmations = Some(latest - number);
+                confirmed.push(opportunity);
+            }
+            Ok(_) => warn!(pair = %opportunity.pair, "Dropping opportunity from block {}, which was reorged out", number),
+            Err(e) => warn!(pair = %opportunity.pair, "Dropping opportunity from block {}, couldn't confirm it: {}", number, e),
+        }
+    }
+    confirmed
+}
+
+/// Fetches the EIP-1559 max and priority fees, or the legacy gas price from nodes without
+/// fee history. The legacy price is retried like `with_retry`; a node that fails the fee
+/// estimate once likely doesn't support it at all.
+async fn get_gas_fees<M: Middleware + 'static>(client: &M, retry: RetryPolicy) -> Result<GasPrice, BotError> {
+    let reason = match tokio::time::timeout(retry.timeout, client.estimate_eip1559_fees(None)).await {
+        Ok(Ok((max_fee, priority_fee))) => return Ok(GasPrice::Eip1559 { max_fee, priority_fee }),
+        Ok(Err(e)) => e.to_string(),
+        Err(_) => BotError::Timeout(retry.timeout).to_string(),
+    };
+    debug!("EIP-1559 fee estimate unavailable, using the legacy gas price: {}", reason);
+    let price = with_retry(retry, move || async move {
+        client.get_gas_price().await.map_err(|e| BotError::Rpc(e.to_string()))
+    })
+    .await?;
+    Ok(GasPrice::Legacy(price))
+}
+
+/// Fetches the node's current gas price in gwei, giving up after `timeout`
+async fn get_gas_price_gwei<M: Middleware + 'static>(client: &M, timeout: Duration) -> Result<f64> {
+    let price = tokio::time::timeout(timeout, client.get_gas_price())
+        .await
+        .map_err(|_| BotError::Timeout(timeout))?
+        .map_err(|e| BotError::Rpc(e.to_string()))?;
+    Ok(from_units(price, GWEI_DECIMALS)?)
+}
+
+/// Fetches the latest block number, giving up after `timeout`
+async fn get_block_number<M: Middleware + 'static>(client: &M, timeout: Duration) -> Result<u64> {
+    let number = tokio::time::timeout(timeout, client.get_block_number())
+        .await
+        .map_err(|_| BotError::Timeout(timeout))??;
+    Ok(number.as_u64())
+}
+
+/// Estimates the gas cost of a trade in `token`. MATIC is priced through `contract` at
+/// `block`, unless `token` is USDC and this tick's MATIC price is known.
+async fn gas_cost_in(
+    token: Address,
+    decimals: u32,
+    contract: &dyn PriceSource,
+    settings: &Settings,
+    network: Network,
+    retry: RetryPolicy,
+    block: Option<BlockId>,
+) -> Result<GasEstimate> {
+    let matic_price = match network.matic_price_usdc {
+        _ if token == settings.wmatic => 1.0,
+        Some(price) if token == settings.usdc => price,
+        _ => {
+            let one_matic = parse_units(1, MATIC_DECIMALS)?.into();
+            quote_at(contract, one_matic, vec![settings.wmatic, token], decimals, retry, block).await?
+        }
+    };
+    let gas_price = network.gas_price.ok_or_else(|| eyre::eyre!("this tick's gas price is unknown"))?;
+    let multiplier = network.priority_fee_multiplier(settings);
+    let mut estimate = estimate_gas_cost(gas_price, matic_price, settings.gas_units, multiplier)?;
+    estimate.utilization = network.utilization;
+    Ok(estimate)
+}
+
+/// Fetches the price of Token A in terms of Token B from a single DEX, at `block` if
+/// given or else the latest block
+async fn get_price(
+    contract: &dyn PriceSource,
+    amount_in: U256,
+    pair: &Pair,
+    retry: RetryPolicy,
+    block: Option<BlockId>,
+) -> Result<f64, BotError> {
+    quote_at(contract, amount_in, pair.path(false), pair.decimals_b, retry, block).await
+}
+
+/// Fetches how much Token A a given amount of Token B buys on a single DEX
+async fn get_price_reversed(
+    contract: &dyn PriceSource,
+    amount_in: U256,
+    pair: &Pair,
+    retry: RetryPolicy,
+    block: Option<BlockId>,
+) -> Result<f64, BotError> {
+    quote_at(contract, amount_in, pair.path(true), pair.decimals_a, retry, block).await
+}
+
+/// Quotes `amount_in` along `path` at `block`, or the latest block if unset, and returns
+/// the output amount as a readable float
+async fn quote_at(
+    contract: &dyn PriceSource,
+    amount_in: U256,
+    path: Vec<Address>,
+    decimals_out: u32,
+    retry: RetryPolicy,
+    block: Option<BlockId>,
+) -> Result<f64, BotError> {
+    let amount = amount_out_with_retry(contract, amount_in, path, retry, block).await?;
+
+    // Convert from WEI/Satoshi format to a readable float
+    from_units(amount, decimals_out)
+}
+
+/// Picks the output amount from a getAmountsOut result, which holds one amount per hop
+/// plus the input. Anything shorter than `[amount_in, amount_out]` is treated as empty.
+fn final_amount(amounts_out: &[U256]) -> Result<U256, BotError> {
+    match amounts_out {
+        [_, .., last] => Ok(*last),
+        _ => Err(BotError::EmptyAmountsOut),
+    }
+}
+
+/// Encodes a V3 swap path: each token address followed by the pool fee to the next one
+fn v3_path(path: &[Address], fee_tier: u32) -> Bytes {
+    let mut encoded = Vec::with_capacity(path.len() * 23);
+    for (i, token) in path.iter().enumerate() {
+        if i > 0 {
+            // uint24, big-endian
+            encoded.extend_from_slice(&fee_tier.to_be_bytes()[1..]);
+        }
+        encoded.extend_from_slice(token.as_bytes());
+    }
+    encoded.into()
+}
+
+/// Quotes a swap, retrying network/provider failures like `with_retry`
+async fn amount_out_with_retry(
+    contract: &dyn PriceSource,
+    amount_in: U256,
+    path: Vec<Address>,
+    retry: RetryPolicy,
+    block: Option<BlockId>,
+) -> Result<U256, BotError> {
+    with_retry(retry, || contract.amount_out(amount_in, &path, block)).await
+}
+
+/// Makes a contract call at `block`, retrying network/provider failures like `with_retry`
+async fn call_with_retry<M: Middleware, D: abi::Detokenize>(
+    call: ContractCall<M, D>,
+    retry: RetryPolicy,
+    block: Option<BlockId>,
+) -> Result<D, BotError> {
+    let call = &at_block(call, block);
+    with_retry(retry, move || async move { call.call().await.map_err(BotError::from) }).await
+}
+
+/// Runs an RPC call, retrying network/provider failures with exponential backoff.
+/// Reverts, decoding errors and timeouts are returned immediately; a timed-out node
+/// is simply tried again on the next tick.
+async fn with_retry<T, F>(retry: RetryPolicy, mut call: impl FnMut() -> F) -> Result<T, BotError>
+where
+    F: std::future::Future<Output = Result<T, BotError>>,
+{
+    let mut attempt = 0;
+    loop {
+        let result = match tokio::time::timeout(retry.timeout, call()).await {
+            Ok(result) => result,
+            Err(_) => Err(BotError::Timeout(retry.timeout)),
+        };
+        match result {
+            Ok(value) => return Ok(value),
+            Err(e) if e.is_transient() && attempt < retry.max_retries => {
+                let delay = retry.base_delay * 2u32.pow(attempt);
+                attempt += 1;
+                debug!("RPC call failed ({}), retry {}/{} in {:?}", e, attempt, retry.max_retries, delay);
+                tokio::time::sleep(delay).await;
+            }
+            Err(e) => return Err(e),
+        }
+    }
+}
+
+/// Buys Token A with `amount_b_in` Token B on one DEX and sells the proceeds on another,
+/// returning the amount of Token B received at the end of the round trip
+async fn round_trip(
+    buy_contract: &dyn PriceSource,
+    sell_contract: &dyn PriceSource,
+    amount_b_in: f64,
+    pair: &Pair,
+    retry: RetryPolicy,
+    block: Option<BlockId>,
+) -> Result<f64, BotError> {
+    let amount_a_bought =
+        get_price_reversed(buy_contract, to_units(amount_b_in, pair.decimals_b)?, pair, retry, block).await?;
+    get_price(sell_contract, to_units(amount_a_bought, pair.decimals_a)?, pair, retry, block).await
+}
+
+/// How much worse the per-unit price of a full-size quote is than that of a tiny
+/// reference quote, in percent. Deep pools stay close to 0.
+fn price_impact_pct(amount_in: f64, amount_out: f64, reference_in: f64, reference_out: f64) -> f64 {
+    let price = amount_out / amount_in;
+    let reference_price = reference_out / reference_in;
+    (1.0 - price / reference_price) * 100.0
+}
+
+/// Estimates the cost of spending `gas_units` at the chain's current gas price,
+/// in whatever token `matic_price` is denominated in
+fn estimate_gas_cost(
+    gas_price: GasPrice,
+    matic_price: f64,
+    gas_units: u64,
+    priority_fee_multiplier: f64,
+) -> Result<GasEstimate> {
+    // Budget for the worst case, the full max fee; nodes without fee history get the legacy price.
+    // Only the priority fee is scaled for congestion, since that's what buys inclusion.
+    let (gas_price, fees) = match gas_price {
+        GasPrice::Eip1559 { max_fee, priority_fee } => {
+            let (max_fee, priority_fee) = scale_priority_fee(max_fee, priority_fee, priority_fee_multiplier)?;
+            let fees = Eip1559Fees {
+                base_fee_gwei: from_units(max_fee.saturating_sub(priority_fee), GWEI_DECIMALS)?,
+                priority_fee_gwei: from_units(priority_fee, GWEI_DECIMALS)?,
+            };
+            (max_fee, Some(fees))
+        }
+        GasPrice::Legacy(gas_price) => (gas_price, None),
+    };
+    let cost_wei = gas_price * U256::from(gas_units);
+    let cost_matic = from_units(cost_wei, MATIC_DECIMALS)?;
+    Ok(GasEstimate {
+        units: gas_units,
+        price_gwei: from_units(gas_price, GWEI_DECIMALS)?,
+        fees,
+        utilization: None,
+        priority_fee_multiplier: if fees.is_some() { priority_fee_multiplier } else { 1.0 },
+        cost_matic,
+        cost: cost_matic * matic_price,
+    })
+}
+
+/// Multiplies the priority fee part of an EIP-1559 max fee, keeping the base fee allowance
+fn scale_priority_fee(max_fee: U256, priority_fee: U256, multiplier: f64) -> Result<(U256, U256), BotError> {
+    if multiplier == 1.0 {
+        return Ok((max_fee, priority_fee));
+    }
+    let scaled = to_units(from_units(priority_fee, 0)? * multiplier, 0)?;
+    Ok((max_fee.saturating_sub(priority_fee) + scaled, scaled))
+}
+
+/// Converts a readable float back into raw token units
+fn to_units(amount: f64, decimals: u32) -> Result<U256, BotError> {
+    // Round to the token's precision first so `parse_units` never sees too many decimals
+    let formatted = format!("{:.*}", decimals as usize, amount);
+    Ok(parse_units(formatted, decimals)?.into())
+}
+
+/// Converts a raw amount with `decimals` to a readable float. Going through the decimal
+/// string handles the full U256 range, losing only what doesn't fit in an f64.
+fn from_units(amount: U256, decimals: u32) -> Result<f64, BotError> {
+    let formatted = format_units(amount, decimals)?;
+    Ok(formatted.parse::<f64>().map_err(ethers::utils::ConversionError::from)?)
+}
+
+/// Prices every combination of two DEXes from their quotes alone, treating the sell quote
+/// as the round trip, and ranks them most profitable first. Each combination appears once,
+/// buying on whichever of the two is cheaper.
+fn rank_opportunities(pair: &Pair, amount_in: f64, prices: &[(&Dex, f64)], costs: Costs) -> Vec<Opportunity> {
+    let mut ranking = Vec::with_capacity(prices.len() * prices.len().saturating_sub(1) / 2);
+    for (i, &(dex_a, price_a)) in prices.iter().enumerate() {
+        for &(dex_b, price_b) in &prices[i + 1..] {
+            // Opportunity: Buy on the cheaper DEX, sell on the more expensive one
+            let (buy, sell) = if price_a < price_b {
+                ((dex_a, price_a), (dex_b, price_b))
+            } else {
+                ((dex_b, price_b), (dex_a, price_a))
+            };
+            ranking.push(price_trade(pair, amount_in, buy, sell, sell.1, costs));
+        }
+    }
+    ranking.sort_by(|a, b| b.net_profit.total_cmp(&a.net_profit));
+    ranking
+}
+
+/// Logs the most profitable DEX combinations of a check, by their quotes
+fn log_ranking(settings: &Settings, pair: &Pair, ranking: &[Opportunity]) {
+    info!("[{}] Top DEX combinations by quoted net profit:", pair.label);
+    for (rank, trade) in ranking.iter().take(RANKING_LOGGED).enumerate() {
+        info!(
+            "  {}. Buy on {}, sell on {}: spread {:.3}%, net profit {:.*} {}",
+            rank + 1,
+            trade.buy_dex,
+            trade.sell_dex,
+            (trade.sell_price - trade.buy_price) / trade.buy_price * 100.0,
+            settings.display_decimals,
+            trade.net_profit,
+            pair.symbol_b()
+        );
+    }
+}
+
+/// Computes the round-trip profit and returns the opportunity if it clears the threshold.
+/// `buy` and `sell` are each DEX's Token B quote for `amount_in` Token A.
+fn check_opportunity(
+    pair: &Pair,
+    amount_in: f64,
+    buy: (&Dex, f64),
+    sell: (&Dex, f64),
+    round_trip_out: f64,
+    costs: Costs,
+    thresholds: Thresholds,
+) -> Option<Opportunity> {
+    let trade = price_trade(pair, amount_in, buy, sell, round_trip_out, costs);
+    thresholds.cleared_by(trade.net_profit, trade.profit_percent).then_some(trade)
+}
+
+/// Works out a round trip's profit after costs, whether or not it clears the thresholds
+fn price_trade(
+    pair: &Pair,
+    amount_in: f64,
+    (buy_dex, buy_price): (&Dex, f64),
+    (sell_dex, sell_price): (&Dex, f64),
+    round_trip_out: f64,
+    costs: Costs,
+) -> Opportunity {
+    let gross_profit = round_trip_out - buy_price;
+    // Each leg pays its own DEX's fee on the Token B value it trades
+    let fees = buy_price * bps(buy_dex.fee_bps) + round_trip_out * bps(sell_dex.fee_bps);
+    // The quote is an ideal fill; assume the sell leg actually receives a little less
+    let slippage = round_trip_out * bps(costs.slippage_bps);
+    // Every taxed transfer moves roughly the Token B value of the trade
+    let transfer_tax = buy_price * bps(costs.tax_bps);
+    let gas_cost = costs.gas;
+    
+    // 5. SIMULATED PROFIT CALCULATION (including fees, slippage, taxes and gas cost) [cite: 10]
+    let net_profit = gross_profit - fees - slippage - transfer_tax - gas_cost;
+    // Relative to what the traded Token A is worth, so runs with different `amount_in` compare
+    let profit_percent = net_profit / buy_price * 100.0;
+
+    Opportunity {
+        id: String::new(),
+        timestamp: Utc::now(),
+        block_number: None,
+        pair: pair.label.clone(),
+        symbol_a: pair.symbol_a().to_string(),
+        symbol_b: pair.symbol_b().to_string(),
+        amount_in,
+        buy_dex: buy_dex.name.clone(),
+        sell_dex: sell_dex.name.clone(),
+        buy_price,
+        sell_price,
+        round_trip_out,
+        gross_profit,
+        fees,
+        slippage,
+        transfer_tax,
+        gas_cost,
+        gas_estimate: costs.gas_estimate,
+        net_profit,
+        net_profit_in_currency: None,
+        profit_percent,
+        price_impact_pct: 0.0,
+        buy_deviation_pct: None,
+        sell_deviation_pct: None,
+        confirmations: None,
+        tier: tier::Tier::Info,
+    }
+}
+
+/// Converts basis points to a fraction, e.g. 30 bps -> 0.003
+fn bps(value: u32) -> f64 {
+    f64::from(value) / 10_000.0
+}
+
+/// Logs a detected opportunity, or prints it as JSON
+fn report_opportunity(settings: &Settings, opportunity: &Opportunity) {
+    match settings.output_format {
+        output::OutputFormat::Text => log_opportunity_banner(settings, opportunity),
+        output::OutputFormat::Json => output::emit(output::Event::Opportunity(opportunity)),
+    }
+}
+
+fn log_opportunity_banner(settings: &Settings, opportunity: &Opportunity) {
+    // Only the headline takes the tier's level, so log filters and aggregators can single
+    // out the strong ones while the details below stay at info
+    macro_rules! headline {
+        ($level:ident) => {
+            tracing::$level!(
+                id = %opportunity.id,
+                tier = %opportunity.tier,
+                pair = %opportunity.pair,
+                block_number = opportunity.block_number,
+                amount_in = opportunity.amount_in,
+                buy_dex = %opportunity.buy_dex,
+                sell_dex = %opportunity.sell_dex,
+                gross_profit = opportunity.gross_profit,
+                fees = opportunity.fees,
+                slippage = opportunity.slippage,
+                gas_cost = opportunity.gas_cost,
+                gas_units = opportunity.gas_estimate.map(|gas| gas.units),
+                gas_price_gwei = opportunity.gas_estimate.map(|gas| gas.price_gwei),
+                raw_net_profit = opportunity.net_profit + opportunity.slippage,
+                net_profit = opportunity.net_profit,
+                net_profit_in_currency = opportunity.net_profit_in_currency,
+                profit_percent = opportunity.profit_percent,
+                price_impact_pct = opportunity.price_impact_pct,
+                "!!! Arbitrage Opportunity Detected! !!!"
+            )
+        };
+    }
+    match opportunity.tier {
+        tier::Tier::Info => headline!(info),
+        tier::Tier::Notable => headline!(warn),
+        tier::Tier::Strong => headline!(error),
+    }
+    info!("  - ID: {}", opportunity.id);
+    info!("  - Tier: {}", opportunity.tier);
+    info!("  - Pair: {}", opportunity.pair);
+    match (opportunity.block_number, opportunity.confirmations) {
+        (Some(block_number), Some(confirmations)) => info!("  - Block: {} ({} confirmations)", block_number, confirmations),
+        (Some(block_number), None) => info!("  - Block: {}", block_number),
+        _ => {}
+    }
+    let (symbol_a, symbol_b) = (&opportunity.symbol_a, &opportunity.symbol_b);
+    let decimals = settings.display_decimals;
+    info!("  - Action: BUY {} {} on {}", opportunity.amount_in, symbol_a, opportunity.buy_dex);
+    info!("  - Action: SELL {} {} on {}", opportunity.amount_in, symbol_a, opportunity.sell_dex);
+    info!(
+        "  - Round Trip: {:.*} {} -> {:.*} {}",
+        decimals, opportunity.buy_price, symbol_b, decimals, opportunity.round_trip_out, symbol_b
+    );
+    info!("  - Est. Gross Profit: {:.*} {}", decimals, opportunity.gross_profit, symbol_b);
+    info!("  - Est. Swap Fees: -{:.*} {}", decimals, opportunity.fees, symbol_b);
+    match opportunity.gas_estimate {
+        Some(gas) => {
+            let price = match gas.fees {
+                Some(fees) => format!(
+                    "({:.2} base + {:.2} priority) gwei",
+                    fees.base_fee_gwei, fees.priority_fee_gwei
+                ),
+                None => format!("{:.2} gwei", gas.price_gwei),
+            };
+            info!(
+                "  - Est. Gas Cost: {} gas * {} = {:.6} MATIC -> -{:.*} {}",
+                gas.units, price, gas.cost_matic, decimals, opportunity.gas_cost, symbol_b
+            );
+            if let Some(utilization) = gas.utilization {
+                info!(
+                    "  - Congestion: latest block {:.0}% full, priority fee x{}",
+                    utilization * 100.0,
+                    gas.priority_fee_multiplier
+                );
+            }
+        }
+        None => info!("  - Est. Gas Cost (simulated): -{:.*} {}", decimals, opportunity.gas_cost, symbol_b),
+    }
+    let quoted_profit = opportunity.net_profit + opportunity.slippage;
+    info!("  - Net Profit at Quoted Prices: {:.*} {}", decimals, quoted_profit, symbol_b);
+    info!("  - Est. Slippage ({} bps): -{:.*} {}", settings.slippage_bps, decimals, opportunity.slippage, symbol_b);
+    if opportunity.transfer_tax > 0.0 {
+        info!("  - Est. Transfer Tax: -{:.*} {}", decimals, opportunity.transfer_tax, symbol_b);
+    }
+    info!(
+        "  - SIMULATED NET PROFIT: {:.*} {} ({:.4}%)",
+        decimals, opportunity.net_profit, symbol_b, opportunity.profit_percent
+    );
+    if let Some(profit) = opportunity.net_profit_in_currency {
+        info!("  - Net Profit in {}: {:.*}", profit_currency(settings, opportunity), decimals, profit);
+    }
+    info!("  - Price Impact: {:.2}%", opportunity.price_impact_pct);
+    if let (Some(buy), Some(sell)) = (opportunity.buy_deviation_pct, opportunity.sell_deviation_pct) {
+        info!("  - Deviation from Reference: buy {:+.2}%, sell {:+.2}%", buy, sell);
+    }
+}
+
+/// The token `net_profit_in_currency` is in, or Token B when it isn't set
+fn profit_currency<'a>(settings: &'a Settings, opportunity: &'a Opportunity) -> &'a str {
+    match (&settings.profit_currency, opportunity.net_profit_in_currency) {
+        (Some(currency), Some(_)) => &currency.label,
+        (None, Some(_)) => &opportunity.symbol_a,
+        (_, None) => &opportunity.symbol_b,
+    }
+}
+
+/// Appends an opportunity to the CSV file, writing the header row if the file is new
+fn log_opportunity(path: &str, record: &OpportunityRecord) -> Result<()> {
+    let mut writer = daily_csv::append_to(Path::new(path), record)?;
+    writer.serialize(record)?;
+    writer.flush()?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn dex(name: &str) -> Dex {
+        Dex {
+            name: name.to_string(),
+            kind: DexKind::V2,
+            router_address: Address::zero(),
+            fee_tier: None,
+            fee_bps: 0,
+            enabled: true,
+        }
+    }
+
+    fn weth_usdc() -> Pair {
+        Pair {
+            label: "WETH/USDC".to_string(),
+            token_a: Address::zero(),
+            token_b: Address::zero(),
+            decimals_a: 18,
+            decimals_b: 6,
+            symbol_a: Some("WETH".to_string()),
+            symbol_b: Some("USDC".to_string()),
+            intermediate_tokens: vec![],
+            profit_in: PairToken::B,
+            invert: false,
+            minimum_profit_threshold: None,
+        }
+    }
+
+    fn costs(gas: f64, slippage_bps: u32) -> Costs {
+        Costs {
+            gas,
+            gas_estimate: None,
+            slippage_bps,
+            tax_bps: 0,
+        }
+    }
+
+    /// A V2 router whose next `getAmountsOut` call answers with `amounts`
+    fn mock_router(amounts: &[U256]) -> Quoter<Provider<MockProvider>> {
+        let (provider, mock) = Provider::mocked();
+        let encoded = ethers::abi::encode(&[ethers::abi::Token::Array(
+            amounts.iter().map(|amount| ethers::abi::Token::Uint(*amount)).collect(),
+        )]);
+        mock.push::<Bytes, _>(Bytes::from(encoded)).unwrap();
+        Quoter::new(&dex("QuickSwap"), Arc::new(provider))
+    }
+
+    fn no_retries() -> RetryPolicy {
+        RetryPolicy {
+            max_retries: 0,
+            base_delay: Duration::ZERO,
+            timeout: Duration::from_secs(5),
+        }
+    }
+
+    /// Runs the same detection the main loop does, treating both quotes as exact round trips
+    fn detect(price_a: f64, price_b: f64, gas_cost: f64, threshold: f64) -> Option<Opportunity> {
+        let (dex_a, dex_b) = (dex("QuickSwap"), dex("SushiSwap"));
+        let prices = [(&dex_a, price_a), (&dex_b, price_b)];
+        let best = rank_opportunities(&weth_usdc(), 1.0, &prices, costs(gas_cost, 0)).remove(0);
+        let dex_named = |name: &str| if name == dex_a.name { &dex_a } else { &dex_b };
+        let (buy_dex, sell_dex) = (dex_named(&best.buy_dex), dex_named(&best.sell_dex));
+        let price_of = |d: &Dex| if std::ptr::eq(d, &dex_a) { price_a } else { price_b };
+        let sell_price = price_of(sell_dex);
+        check_opportunity(
+            &weth_usdc(),
+            1.0,
+            (buy_dex, price_of(buy_dex)),
+            (sell_dex, sell_price),
+            sell_price,
+            costs(gas_cost, 0),
+            Thresholds {
+                minimum_profit: threshold,
+                minimum_profit_percent: None,
+            },
+        )
+    }
+
+    /// Deserializes settings the way `load_settings` does, from an inline TOML document
+    fn parse_settings(toml: &str) -> Settings {
+        config::Config::builder()
+            .add_source(config::File::from_str(toml, config::FileFormat::Toml))
+            .build()
+            .unwrap()
+            .try_deserialize()
+            .unwrap()
+    }
+
+    const VALID_SETTINGS: &str = r#"
+        check_interval_seconds = 10
+        minimum_profit_threshold = 5.0
+        amount_in = 1.0
+        simulated_gas_cost_usdc = 0.5
+        wmatic = "0x0d500B1d8E8eF31E21C99d1Db9A6444d3ADf1270"
+
+        [[pairs]]
+        label = "WETH/USDC"
+        token_a = "0x7ceB23fD6bC0adD59E62ac25578270cFf1b9f619"
+        token_b = "0x2791Bca1f2de4661ED88A30C99A7a9449Aa84174"
+        decimals_a = 18
+        decimals_b = 6
+
+        [[dexes]]
+        name = "QuickSwap"
+        router_address = "0xa5E0829CaCEd8fFDD4De3c43696c57F7D7A678ff"
+
+        [[dexes]]
+        name = "SushiSwap"
+        router_address = "0x1b02dA8Cb0d097eB8D57A175b88c7D8b47997506"
+    "#;
+
+    #[tokio::test]
+    async fn reports_configured_addresses_without_contract_code() {
+        let settings = parse_settings(VALID_SETTINGS);
+        let (provider, mock) = Provider::mocked();
+        // Checked in order: both routers, the pair's tokens (USDC is also `usdc`), then
+        // WMATIC. Mocked responses are returned last in, first out.
+        let code = Bytes::from(vec![0x60, 0x80]);
+        for response in [code.clone(), code.clone(), code.clone(), Bytes::new(), code] {
+            mock.push::<Bytes, _>(response).unwrap();
+        }
+
+        let missing = registry::missing_contracts(&settings, &settings.enabled_dexes(), &provider).await;
+        assert_eq!(
+            missing,
+            vec!["0x1b02da8cb0d097eb8d57a175b88c7d8b47997506 (router of SushiSwap) has no contract code"]
+        );
+    }
+
+    #[tokio::test]
+    async fn get_price_decodes_the_final_amount_in_token_b_units() {
+        let one_weth = U256::exp10(18);
+        let router = mock_router(&[one_weth, U256::from(3_412_500_000u64)]);
+
+        let price = get_price(&router, one_weth, &weth_usdc(), no_retries(), None).await.unwrap();
+        assert_eq!(price, 3412.5);
+    }
+
+    #[tokio::test]
+    async fn get_price_quotes_at_the_requested_block() {
+        let (provider, mock) = Provider::mocked();
+        let encoded = ethers::abi::encode(&[ethers::abi::Token::Array(vec![
+            ethers::abi::Token::Uint(U256::exp10(18)),
+            ethers::abi::Token::Uint(U256::from(3_400_000_000u64)),
+        ])]);
+        mock.push::<Bytes, _>(Bytes::from(encoded)).unwrap();
+        let router = Quoter::new(&dex("QuickSwap"), Arc::new(provider));
+        let block = BlockId::from(50_000_000u64);
+
+        let price = get_price(&router, U256::exp10(18), &weth_usdc(), no_retries(), Some(block)).await.unwrap();
+        assert_eq!(price, 3400.0);
+        let Quoter::V2(contract) = &router else { unreachable!() };
+        let call = contract.get_amounts_out(U256::exp10(18), weth_usdc().path(false));
+        mock.assert_request("eth_call", (call.tx, block)).unwrap();
+    }
+
+    #[tokio::test]
+    async fn get_price_rejects_an_empty_amounts_out() {
+        let router = mock_router(&[]);
+
+        let result = get_price(&router, U256::exp10(18), &weth_usdc(), no_retries(), None).await;
+        assert!(matches!(result, Err(BotError::EmptyAmountsOut)));
+    }
+
+    /// Fails with an RPC error `failures` times, then quotes `amount_out`
+    struct FlakySource {
+        failures: std::sync::atomic::AtomicU32,
+        amount_out: U256,
+    }
+
+    #[async_trait::async_trait]
+    impl PriceSource for FlakySource {
+        async fn amount_out(&self, _: U256, _: &[Address], _: Option<BlockId>) -> Result<U256, BotError> {
+            let remaining = self.failures.load(std::sync::atomic::Ordering::SeqCst);
+            if remaining > 0 {
+                self.failures.store(remaining - 1, std::sync::atomic::Ordering::SeqCst);
+                return Err(BotError::Rpc("connection reset".to_string()));
+            }
+            Ok(self.amount_out)
+        }
+    }
+
+    #[tokio::test]
+    async fn get_price_retries_a_flaky_source() {
+        let source = FlakySource {
+            failures: 2.into(),
+            amount_out: U256::from(3_400_000_000u64),
+        };
+        let retry = RetryPolicy {
+            max_retries: 2,
+            ..no_retries()
+        };
+
+        assert_eq!(get_price(&source, U256::exp10(18), &weth_usdc(), retry, None).await.unwrap(), 3400.0);
+        source.failures.store(1, std::sync::atomic::Ordering::SeqCst);
+        assert!(get_price(&source, U256::exp10(18), &weth_usdc(), no_retries(), None).await.is_err());
+    }
+
+    /// Quotes 1 Token A at 3,400 Token B both ways, but reverts selling more than `max_in` Token A
+    struct ThinPoolSource {
+        token_a: Address,
+        max_in: U256,
+    }
+
+    #[async_trait::async_trait]
+    impl PriceSource for ThinPoolSource {
+        async fn amount_out(&self, amount_in: U256, path: &[Address], _: Option<BlockId>) -> Result<U256, BotError> {
+            if path[0] != self.token_a {
+                return Ok(amount_in * U256::exp10(12) / 3_400);
+            }
+            if amount_in > self.max_in {
+                return Err(BotError::Reverted("UniswapV2Library: INSUFFICIENT_LIQUIDITY".to_string()));
+            }
+            Ok(amount_in * 3_400 / U256::exp10(12))
+        }
+    }
+
+    #[tokio::test]
+    async fn keeps_the_other_trade_sizes_when_one_fails() {
+        let settings = parse_settings(&VALID_SETTINGS.replace("amount_in = 1.0", "amount_in = [1.0, 5.0]"));
+        let pair = settings.pairs[0].clone();
+        let contracts = settings
+            .enabled_dexes()
+            .into_iter()
+            .map(|dex| {
+                let source: Box<dyn PriceSource> = Box::new(ThinPoolSource {
+                    token_a: pair.token_a,
+                    max_in: U256::exp10(18) * 2,
+                });
+                (dex, source)
+            })
+            .collect();
+        let (provider, _) = Provider::mocked();
+        let markets = registry::MarketRegistry::with_sources(contracts, Arc::new(provider));
+        let metrics = metrics::Metrics::new();
+        let clock = clock::MockClock::new(1_700_000_000);
+        let scanner = Scanner {
+            settings: &settings,
+            markets: &markets,
+            block_number: None,
+            at_block: None,
+            block_timestamp: None,
+            network: Network::default(),
+            retry: no_retries(),
+            sinks: &[],
+            metrics: &metrics,
+            benchmark: None,
+            freshness: None,
+            clock: &clock,
+            reference_prices: None,
+        };
+
+        let scans = scanner.scan_pair(&pair).await.unwrap();
+        assert_eq!(scans.iter().map(|scan| scan.trade_size).collect::<Vec<_>>(), vec![1.0]);
+
+        let settings = parse_settings(&VALID_SETTINGS.replace("amount_in = 1.0", "amount_in = [3.0, 5.0]"));
+        let scanner = Scanner { settings: &settings, ..scanner };
+        assert!(scanner.scan_pair(&pair).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn simulated_swaps_surface_the_revert_reason() {
+        use ethers::providers::{JsonRpcError, MockResponse};
+
+        let (provider, mock) = Provider::mocked();
+        // Error(string) with "UniswapV2: K"
+        let revert = ethers::abi::encode(&[ethers::abi::Token::String("UniswapV2: K".to_string())]);
+        let data = format!("0x08c379a0{}", ethers::utils::hex::encode(revert));
+        mock.push_response(MockResponse::Error(JsonRpcError {
+            code: 3,
+            message: "execution reverted: UniswapV2: K".to_string(),
+            data: Some(serde_json::Value::String(data)),
+        }));
+
+        let path = weth_usdc().path(false);
+        let clock = clock::MockClock::new(1_700_000_000);
+        // Retries are allowed but a revert isn't retried: the mock has no second response
+        let retry = RetryPolicy { max_retries: 2, ..no_retries() };
+        let block = BlockId::from(50_000_000u64);
+        let swap = SwapCall { from: Address::zero(), block: Some(block), retry, clock: &clock };
+        let client = Arc::new(provider);
+        let result = simulate_swap(&dex("QuickSwap"), client.clone(), U256::exp10(18), path.clone(), swap).await;
+        assert_eq!(result.unwrap_err().to_string(), "reverted: UniswapV2: K");
+        // Simulated at the quotes' block, with the deadline taken from the clock
+        let router = IUniswapV2Router02::new(dex("QuickSwap").router_address, client);
+        let deadline = U256::from(1_700_000_300u64);
+        let call = router.swap_exact_tokens_for_tokens(U256::exp10(18), U256::zero(), path, Address::zero(), deadline);
+        mock.assert_request("eth_call", (call.from(Address::zero()).tx, block)).unwrap();
+    }
+
+    #[tokio::test]
+    async fn reads_the_token_b_side_of_the_pool_reserves_and_last_trade() {
+        use ethers::abi::{encode, Token};
+        use ethers::providers::{JsonRpcError, MockResponse};
+
+        let [token_a, token_b, factory, pool] = [1u64, 2, 3, 4].map(Address::from_low_u64_be);
+        let pair = Pair { token_a, token_b, ..weth_usdc() };
+        let (provider, mock) = Provider::mocked();
+        // Responses are served last pushed first: factory(), getPair, token0, getReserves
+        let reserves = [U256::from(250_000_000_000u64), U256::exp10(20), U256::from(1_700_000_000u32)];
+        mock.push::<Bytes, _>(Bytes::from(encode(&reserves.map(Token::Uint)))).unwrap();
+        mock.push::<Bytes, _>(Bytes::from(encode(&[Token::Address(token_b)]))).unwrap();
+        mock.push::<Bytes, _>(Bytes::from(encode(&[Token::Address(pool)]))).unwrap();
+        mock.push::<Bytes, _>(Bytes::from(encode(&[Token::Address(factory)]))).unwrap();
+        // The first factory() call hits a flaky node and is retried
+        mock.push_response(MockResponse::Error(JsonRpcError {
+            code: -32000,
+            message: "header not found".to_string(),
+            data: None,
+        }));
+        let retry = RetryPolicy { max_retries: 1, ..no_retries() };
+        let block = BlockId::from(50_000_000u64);
+
+        let client = Arc::new(provider);
+        let pool = pool_state(&dex("QuickSwap"), client.clone(), &pair, retry, Some(block)).await.unwrap();
+        assert_eq!(pool.reserve_b, 250_000.0);
+        assert_eq!(pool.last_active, 1_700_000_000);
+        // Read at the same block as the quotes
+        let router = IUniswapV2Router02::new(dex("QuickSwap").router_address, client);
+        let factory_call = router.factory();
+        mock.assert_request("eth_call", (factory_call.tx.clone(), block)).unwrap();
+        mock.assert_request("eth_call", (factory_call.tx, block)).unwrap();
+    }
+
+    #[tokio::test]
+    async fn measures_pool_idle_time_at_the_quoted_block() {
+        let (provider, mock) = Provider::mocked();
+        let block = ethers::types::Block::<H256> { timestamp: U256::from(1_600_000_000u64), ..Default::default() };
+        mock.push(block).unwrap();
+        let timestamp = get_block_timestamp(&provider, 50_000_000, Duration::from_secs(1)).await.unwrap();
+        mock.assert_request("eth_getBlockByNumber", (BlockNumber::from(50_000_000u64), false)).unwrap();
+
+        // A pool that last traded an hour before the block, long before the clock's time
+        let pool = PoolState { reserve_b: 0.0, last_active: 1_599_996_400 };
+        let clock = clock::MockClock::new(1_700_000_000);
+        let at_block = Some(BlockId::from(50_000_000u64));
+        assert_eq!(pool.idle_seconds(quoted_at(at_block, Some(timestamp), &clock).unwrap()), 3_600);
+        assert_eq!(pool.idle_seconds(quoted_at(None, None, &clock).unwrap()), 100_003_600);
+        assert!(quoted_at(at_block, None, &clock).is_err());
+    }
+
+    #[tokio::test]
+    async fn round_trip_quotes_both_legs() {
+        let pair = weth_usdc();
+        // Buy: 3,400 USDC -> 1.0 WETH on one router, then sell: 1.0 WETH -> 3,410 USDC
+        let buy = mock_router(&[U256::from(3_400_000_000u64), U256::exp10(18)]);
+        let sell = mock_router(&[U256::exp10(18), U256::from(3_410_000_000u64)]);
+
+        let amount_out = round_trip(&buy, &sell, 3400.0, &pair, no_retries(), None).await.unwrap();
+        assert_eq!(amount_out, 3410.0);
+    }
+
+    #[tokio::test]
+    async fn inverted_pairs_quote_and_profit_in_the_other_direction() {
+        let [weth, usdc, wmatic] = [1u64, 2, 3].map(Address::from_low_u64_be);
+        let configured = Pair {
+            token_a: weth,
+            token_b: usdc,
+            intermediate_tokens: vec![wmatic],
+            invert: true,
+            ..weth_usdc()
+        };
+        let pair = configured.clone().oriented();
+        assert_eq!(pair.path(false), configured.path(true));
+        assert_eq!((pair.decimals_a, pair.decimals_b), (6, 18));
+        assert_eq!((pair.symbol_a(), pair.symbol_b()), ("USDC", "WETH"));
+        // Profits are still reported in USDC, now the input token
+        assert_eq!(pair.profit_in, PairToken::A);
+        assert_eq!(Pair { invert: false, ..configured.clone() }.oriented(), Pair { invert: false, ..configured });
+
+        // Buy: 1.0 WETH -> 3,400 USDC on one router, then sell: 3,400 USDC -> 1.01 WETH
+        let buy = mock_router(&[U256::exp10(18), U256::from(3_400_000_000u64)]);
+        let sell = mock_router(&[U256::from(3_400_000_000u64), U256::exp10(16) * 101]);
+        let amount_out = round_trip(&buy, &sell, 1.0, &pair, no_retries(), None).await.unwrap();
+        assert_eq!(amount_out, 1.01);
+
+        let (buy_dex, sell_dex) = (dex("QuickSwap"), dex("SushiSwap"));
+        let thresholds = Thresholds {
+            minimum_profit: 0.001,
+            minimum_profit_percent: None,
+        };
+        let opportunity =
+            check_opportunity(&pair, 3400.0, (&buy_dex, 1.0), (&sell_dex, 1.01), amount_out, costs(0.002, 0), thresholds).unwrap();
+        assert!((opportunity.net_profit - 0.008).abs() < 1e-9);
+        assert_eq!((opportunity.symbol_a.as_str(), opportunity.symbol_b.as_str()), ("USDC", "WETH"));
+    }
+
+    #[test]
+    fn environment_variables_override_the_file() {
+        let file = config::File::from_str(VALID_SETTINGS, config::FileFormat::Toml);
+        let vars = [
+            ("ARB_MINIMUM_PROFIT_THRESHOLD", "2.5"),
+            ("ARB_RPC_URLS", "https://a.example,https://b.example"),
+            ("ARB_AMOUNT_SWEEP__MIN", "0.5"),
+            ("ARB_AMOUNT_SWEEP__MAX", "2"),
+            ("ARB_AMOUNT_SWEEP__STEPS", "3"),
+            ("UNRELATED", "1"),
+        ]
+        .map(|(name, value)| (name.to_string(), value.to_string()));
+
+        let settings = settings_from(file, vars).unwrap();
+        assert_eq!(settings.minimum_profit_threshold, 2.5);
+        assert_eq!(settings.rpc_urls, vec!["https://a.example", "https://b.example"]);
+        assert_eq!(settings.trade_sizes(), vec![0.5, 1.25, 2.0]);
+        assert_eq!(settings.check_interval_seconds, 10);
+    }
+
+    #[test]
+    fn loads_settings_from_the_environment_alone() {
+        let file = config::File::from_str("", config::FileFormat::Toml);
+        let vars = [
+            ("ARB_CHECK_INTERVAL_SECONDS", "30"),
+            ("ARB_MINIMUM_PROFIT_THRESHOLD", "1"),
+            ("ARB_AMOUNT_IN", "1"),
+            ("ARB_SIMULATED_GAS_COST_USDC", "0.5"),
+            ("ARB_WMATIC", "0x0d500B1d8E8eF31E21C99d1Db9A6444d3ADf1270"),
+            (
+                "ARB_PAIRS",
+                r#"[{"label": "WETH/USDC", "token_a": "0x7ceB23fD6bC0adD59E62ac25578270cFf1b9f619",
+                    "token_b": "0x2791Bca1f2de4661ED88A30C99A7a9449Aa84174", "decimals_a": 18, "decimals_b": 6}]"#,
+            ),
+            (
+                "ARB_DEXES",
+                r#"[{"name": "QuickSwap", "router_address": "0xa5E0829CaCEd8fFDD4De3c43696c57F7D7A678ff"},
+                    {"name": "SushiSwap", "router_address": "0x1b02dA8Cb0d097eB8D57A175b88c7D8b47997506"}]"#,
+            ),
+        ]
+        .map(|(name, value)| (name.to_string(), value.to_string()));
+
+        let settings = settings_from(file, vars).unwrap();
+        assert_eq!(settings.check_interval_seconds, 30);
+        assert_eq!(settings.pairs[0].label, "WETH/USDC");
+        assert_eq!(settings.dexes[1].name, "SushiSwap");
+        assert_eq!(validate(&settings), Ok(()));
+    }
+
+    #[test]
+    fn parses_every_field_of_a_representative_settings_file() {
+        let settings = parse_settings(
+            r#"
+            check_interval_seconds = 15
+            minimum_profit_threshold = 2.5
+            minimum_profit_bps = 20
+            amount_in = [0.5, 1.0]
+            simulated_gas_cost_usdc = 0.75
+            gas_units = 250000
+            max_retries = 5
+            rpc_urls = ["https://polygon-rpc.com", "https://rpc.ankr.com/polygon"]
+            provider_pool_size = 2
+            slippage_bps = 15
+            output_format = "json"
+            sinks = ["csv"]
+            output_csv = "found.csv"
+            wmatic = "0x0d500B1d8E8eF31E21C99d1Db9A6444d3ADf1270"
+            triangular = true
+
+            [tiers]
+            notable = 10.0
+            strong = 50.0
+
+            [[notifiers]]
+            kind = "slack"
+            webhook_url = "https://hooks.slack.com/services/T000/B000/XXX"
+            min_tier = "notable"
+
+            [[token_taxes]]
+            token = "0x2791Bca1f2de4661ED88A30C99A7a9449Aa84174"
+            tax_bps = 100
+
+            [[pairs]]
+            label = "WETH/USDC"
+            token_a = "0x7ceB23fD6bC0adD59E62ac25578270cFf1b9f619"
+            token_b = "0x2791Bca1f2de4661ED88A30C99A7a9449Aa84174"
+            decimals_a = 18
+            decimals_b = 6
+            symbol_a = "WETH"
+            intermediate_tokens = ["0x0d500B1d8E8eF31E21C99d1Db9A6444d3ADf1270"]
+            minimum_profit_threshold = 4.0
+            profit_in = "a"
+
+            [[cycles]]
+            label = "WETH/USDC/WMATIC"
+            tokens = [
+                "0x7ceB23fD6bC0adD59E62ac25578270cFf1b9f619",
+                "0x2791Bca1f2de4661ED88A30C99A7a9449Aa84174",
+                "0x0d500B1d8E8eF31E21C99d1Db9A6444d3ADf1270",
+            ]
+            decimals = 18
+
+            [[dexes]]
+            name = "QuickSwap"
+            router_address = "0xa5E0829CaCEd8fFDD4De3c43696c57F7D7A678ff"
+            fee_bps = 5
+
+            [[dexes]]
+            name = "Uniswap V3"
+            kind = "v3"
+            router_address = "0x61fFE014bA17989E743c5F6cB21bF9697530B21e"
+            fee_tier = 500
+            enabled = false
+            "#,
+        );
+        let (weth, usdc, wmatic) = (
+            "0x7ceB23fD6bC0adD59E62ac25578270cFf1b9f619".parse::<Address>().unwrap(),
+            "0x2791Bca1f2de4661ED88A30C99A7a9449Aa84174".parse::<Address>().unwrap(),
+            "0x0d500B1d8E8eF31E21C99d1Db9A6444d3ADf1270".parse::<Address>().unwrap(),
+        );
+
+        assert_eq!(settings.check_interval_seconds, 15);
+        assert_eq!(settings.minimum_profit_threshold, 2.5);
+        assert_eq!(settings.minimum_profit_bps, Some(20));
+        assert_eq!(settings.amount_in, Some(AmountIn::Each(vec![0.5, 1.0])));
+        assert_eq!(settings.simulated_gas_cost_usdc, 0.75);
+        assert_eq!(settings.gas_units, 250_000);
+        assert_eq!(settings.max_retries, 5);
+        assert_eq!(settings.rpc_urls, ["https://polygon-rpc.com", "https://rpc.ankr.com/polygon"]);
+        assert_eq!(settings.provider_pool_size, 2);
+        assert_eq!(settings.slippage_bps, 15);
+        assert_eq!(settings.output_format, output::OutputFormat::Json);
+        assert_eq!(settings.sinks, Some(vec![sink::SinkKind::Csv]));
+        assert_eq!(settings.output_csv.as_deref(), Some("found.csv"));
+        assert_eq!(settings.wmatic, wmatic);
+        assert!(settings.triangular);
+        assert_eq!(settings.tiers, Some(tier::Tiers { notable: 10.0, strong: 50.0 }));
+        assert_eq!(settings.notifiers[0].min_tier, tier::Tier::Notable);
+        assert!(matches!(
+            &settings.notifiers[0].config,
+            notify::NotifierConfig::Slack { webhook_url } if webhook_url.ends_with("/XXX")
+        ));
+        assert_eq!((settings.token_taxes[0].token, settings.token_taxes[0].tax_bps), (usdc, 100));
+
+        let pair = &settings.pairs[0];
+        assert_eq!(pair.label, "WETH/USDC");
+        assert_eq!((pair.token_a, pair.token_b, pair.decimals_a, pair.decimals_b), (weth, usdc, 18, 6));
+        assert_eq!((pair.symbol_a.as_deref(), pair.symbol_b.as_deref()), (Some("WETH"), None));
+        assert_eq!(pair.intermediate_tokens, [wmatic]);
+        assert_eq!(pair.minimum_profit_threshold, Some(4.0));
+        assert_eq!(pair.profit_in, PairToken::A);
+        assert!(!pair.invert);
+
+        let cycle = &settings.cycles[0];
+        assert_eq!((cycle.label.as_str(), cycle.decimals, cycle.symbol.as_deref()), ("WETH/USDC/WMATIC", 18, None));
+        assert_eq!(cycle.tokens, [weth, usdc, wmatic]);
+
+        let (quickswap, uniswap) = (&settings.dexes[0], &settings.dexes[1]);
+        assert_eq!((quickswap.name.as_str(), quickswap.kind, quickswap.fee_bps), ("QuickSwap", DexKind::V2, 5));
+        assert_eq!((quickswap.fee_tier, quickswap.enabled), (None, true));
+        assert_eq!((uniswap.name.as_str(), uniswap.kind, uniswap.fee_tier), ("Uniswap V3", DexKind::V3, Some(500)));
+        assert!(!uniswap.enabled);
+
+        // Everything left out takes its default
+        assert_eq!((settings.run_once, settings.max_concurrency, settings.display_decimals), (false, 4, 4));
+        assert_eq!(settings.usdc, default_usdc());
+        assert_eq!(settings.on_decimals_mismatch, DecimalsMismatch::Warn);
+        assert_eq!(settings.bind_address, IpAddr::from([127, 0, 0, 1]));
+        let exposed = parse_settings(&format!("bind_address = \"0.0.0.0\"\n{}", VALID_SETTINGS));
+        assert_eq!(exposed.bind_address, IpAddr::from([0, 0, 0, 0]));
+    }
+
+    #[test]
+    fn the_sample_settings_file_loads_and_validates() {
+        let settings = parse_settings(include_str!("../config/settings.toml"));
+        assert_eq!(validate(&settings), Ok(()));
+        assert_eq!(settings.pairs[0].label, "WETH/USDC");
+        assert_eq!(settings.dexes.len(), 2);
+    }
+
+    #[test]
+    fn quotes_price_confirmations_blocks_behind_the_latest() {
+        let mut settings = parse_settings(VALID_SETTINGS);
+        assert_eq!(settings.price_block(Some(1_000)), None);
+        settings.price_confirmations = 3;
+        assert_eq!(settings.price_block(Some(1_000)), Some(997));
+        assert_eq!(settings.price_block(None), None);
+        settings.at_block = Some(500);
+        assert_eq!(settings.price_block(Some(1_000)), Some(500));
+
+        settings.confirmations = Some(2);
+        assert!(validate(&settings).is_err());
+    }
+
+    #[test]
+    fn rejects_confirmations_for_a_single_check() {
+        let mut settings = parse_settings(VALID_SETTINGS);
+        settings.confirmations = Some(2);
+        assert_eq!(validate(&settings), Ok(()));
+        settings.run_once = true;
+        assert_eq!(validate(&settings).unwrap_err().len(), 1);
+        // A past block needs no waiting, so `confirmations` is ignored there
+        settings.at_block = Some(500);
+        assert_eq!(validate(&settings), Ok(()));
+    }
+
+    #[test]
+    fn names_a_missing_required_setting() {
+        let toml = VALID_SETTINGS.replace("minimum_profit_threshold = 5.0", "");
+        let file = config::File::from_str(&toml, config::FileFormat::Toml);
+        let error = settings_from(file, []).unwrap_err().to_string();
+        assert!(error.contains("missing field `minimum_profit_threshold`"), "{}", error);
+    }
+
+    #[test]
+    fn converts_amounts_beyond_u128_without_panicking() {
+        assert_eq!(from_units(U256::from(3_412_500_000u64), 6).unwrap(), 3412.5);
+
+        let near_max = U256::from(u128::MAX - 1);
+        let expected = (u128::MAX - 1) as f64 / 1e18;
+        assert!((from_units(near_max, 18).unwrap() - expected).abs() / expected < 1e-12);
+
+        let beyond = U256::from(u128::MAX) * U256::from(1_000u64);
+        assert!((from_units(beyond, 18).unwrap() - expected * 1_000.0).abs() / (expected * 1_000.0) < 1e-12);
+        assert!(from_units(U256::MAX, 0).unwrap().is_finite());
+    }
+
+    #[tokio::test]
+    async fn falls_back_to_the_retried_legacy_gas_price() {
+        use ethers::providers::{JsonRpcError, MockResponse};
+
+        let (provider, mock) = Provider::mocked();
+        let gwei_30 = U256::from(30_000_000_000u64);
+        let error = |message: &str| {
+            MockResponse::Error(JsonRpcError { code: -32000, message: message.to_string(), data: None })
+        };
+        // Served last pushed first: the fee estimate fails, then eth_gasPrice once before answering
+        mock.push(gwei_30).unwrap();
+        mock.push_response(error("connection reset"));
+        mock.push_response(error("the method eth_feeHistory does not exist"));
+
+        let retry = RetryPolicy { max_retries: 1, ..no_retries() };
+        let gas_price = get_gas_fees(&provider, retry).await.unwrap();
+        assert_eq!(gas_price, GasPrice::Legacy(gwei_30));
+        let estimate = estimate_gas_cost(gas_price, 0.5, 300_000, 2.0).unwrap();
+        // 0.009 MATIC at $0.5, and a legacy price isn't scaled for congestion
+        assert_eq!((estimate.price_gwei, estimate.priority_fee_multiplier), (30.0, 1.0));
+        assert!((estimate.cost - 0.0045).abs() < 1e-12);
+    }
+
+    #[test]
+    fn scales_only_the_priority_fee_when_blocks_are_congested() {
+        let mut settings = parse_settings(VALID_SETTINGS);
+        let network = |utilization| Network { matic_price_usdc: None, utilization, gas_price: None };
+        assert_eq!(network(Some(0.95)).priority_fee_multiplier(&settings), 1.0);
+
+        settings.congestion_factor = Some(2.0);
+        assert_eq!(network(Some(0.5)).priority_fee_multiplier(&settings), 1.0);
+        assert_eq!(network(None).priority_fee_multiplier(&settings), 1.0);
+        assert_eq!(network(Some(0.8)).priority_fee_multiplier(&settings), 2.0);
+
+        // 100 gwei base allowance + 30 gwei tip, with the tip doubled
+        let gwei = |n: u64| U256::from(n) * U256::exp10(9);
+        assert_eq!(scale_priority_fee(gwei(130), gwei(30), 2.0).unwrap(), (gwei(160), gwei(60)));
+        assert_eq!(scale_priority_fee(gwei(130), gwei(30), 1.0).unwrap(), (gwei(130), gwei(30)));
+    }
+
+    #[test]
+    fn costs_fall_back_to_the_simulated_gas_cost() {
+        let settings = parse_settings(VALID_SETTINGS);
+        let estimate = GasEstimate {
+            units: 300_000,
+            price_gwei: 40.0,
+            fees: None,
+            utilization: None,
+            priority_fee_multiplier: 1.0,
+            cost_matic: 0.012,
+            cost: 0.006,
+        };
+
+        let live = Costs::new(Some(estimate), &settings);
+        assert_eq!((live.gas, live.gas_estimate), (0.006, Some(estimate)));
+        let simulated = Costs::new(None, &settings);
+        assert_eq!((simulated.gas, simulated.gas_estimate), (settings.simulated_gas_cost_usdc, None));
+    }
+
+    #[test]
+    fn redacts_secrets_from_printed_settings() {
+        let mut settings = parse_settings(VALID_SETTINGS);
+        settings.rpc_urls = vec![
+            "https://polygon-rpc.com".to_string(),
+            "https://polygon-mainnet.g.alchemy.com/v2/secret-key".to_string(),
+            "wss://node.example/ws?token=secret".to_string(),
+        ];
+        settings.discord_webhook_url = Some("https://discord.com/api/webhooks/1/secret".to_string());
+        settings.notifiers = vec![notify::NotifierEntry {
+            config: notify::NotifierConfig::Webhook { url: "https://hooks.example/secret".to_string() },
+            min_tier: tier::Tier::Info,
+        }];
+
+        let printed = serde_json::to_string(&redacted(settings)).unwrap();
+        assert!(!printed.contains("secret"));
+        assert!(printed.contains("https://polygon-rpc.com"));
+        assert!(printed.contains("https://polygon-mainnet.g.alchemy.com/<redacted>"));
+    }
+
+    #[test]
+    fn builds_the_listed_notifiers() {
+        let toml = format!(
+            "{}\n{}",
+            VALID_SETTINGS,
+            r#"
+            [[notifiers]]
+            kind = "slack"
+            webhook_url = "https://hooks.slack.com/services/T/B/X"
+
+            [[notifiers]]
+            kind = "webhook"
+            url = "https://example.com/alerts"
+
+            [[notifiers]]
+            kind = "telegram"
+            bot_token = "123:abc"
+            chat_id = "42"
+            min_tier = "strong"
+            "#
+        );
+        let settings = parse_settings(&toml);
+        let channels: Vec<_> =
+            settings.notifiers.iter().filter_map(|entry| entry.build(settings.display_decimals, &|_| None)).collect();
+        let names: Vec<_> = channels.iter().map(|channel| (channel.notifier.name(), channel.min_tier)).collect();
+        assert_eq!(
+            names,
+            [("Slack", tier::Tier::Info), ("Webhook", tier::Tier::Info), ("Telegram", tier::Tier::Strong)]
+        );
+    }
+
+    #[test]
+    fn builds_the_env_credentials_only_once_for_a_listed_entry() {
+        let env = |name: &str| match name {
+            "TELEGRAM_BOT_TOKEN" => Some("123:abc".to_string()),
+            "TELEGRAM_CHAT_ID" => Some("42".to_string()),
+            _ => None,
+        };
+        let mut settings = parse_settings(VALID_SETTINGS);
+        settings.discord_webhook_url = Some("https://discord.com/api/webhooks/1/secret".to_string());
+        let channels = |settings: &Settings| -> Vec<_> {
+            notifier_channels(settings, env).iter().map(|channel| (channel.notifier.name(), channel.min_tier)).collect()
+        };
+        assert_eq!(channels(&settings), [("Telegram", tier::Tier::Info), ("Discord", tier::Tier::Info)]);
+
+        settings = parse_settings(&format!(
+            "{}
+{}",
+            VALID_SETTINGS,
+            r#"
+            [[notifiers]]
+            kind = "telegram"
+            min_tier = "strong"
+
+            [[notifiers]]
+            kind = "discord"
+            webhook_url = "https://discord.com/api/webhooks/2/other"
+            min_tier = "notable"
+            "#
+        ));
+        settings.discord_webhook_url = Some("https://discord.com/api/webhooks/1/secret".to_string());
+        assert_eq!(channels(&settings), [("Telegram", tier::Tier::Strong), ("Discord", tier::Tier::Notable)]);
+    }
+
+    #[test]
+    fn tiers_opportunities_by_the_profit_they_were_thresholded_in() {
+        let mut settings = parse_settings(&format!("{}\n[tiers]\nnotable = 10.0\nstrong = 50.0\n", VALID_SETTINGS));
+        assert_eq!(validate(&settings), Ok(()));
+        let (buy, sell) = (dex("QuickSwap"), dex("SushiSwap"));
+        let thresholds = settings.thresholds();
+        let opportunity =
+            check_opportunity(&weth_usdc(), 1.0, (&buy, 1000.0), (&sell, 1020.0), 1020.0, costs(2.0, 0), thresholds).unwrap();
+        assert_eq!(settings.tier_of(&opportunity), tier::Tier::Notable);
+        let converted = Opportunity { net_profit_in_currency: Some(60.0), ..opportunity.clone() };
+        assert_eq!(settings.tier_of(&converted), tier::Tier::Strong);
+
+        settings.tiers = None;
+        assert_eq!(settings.tier_of(&converted), tier::Tier::Info);
+        settings.tiers = Some(tier::Tiers { notable: 50.0, strong: 10.0 });
+        assert_eq!(validate(&settings).unwrap_err().len(), 1);
+    }
+
+    #[test]
+    fn reload_applies_thresholds_and_flags_dex_changes() {
+        let mut settings = parse_settings(VALID_SETTINGS);
+        let mut new = settings.clone();
+        new.minimum_profit_threshold = 7.5;
+        new.check_interval_seconds = 60;
+        assert!(reload::restart_required(&settings, &new).is_empty());
+
+        new.dexes[0].router_address = Address::repeat_byte(1);
+        assert_eq!(reload::restart_required(&settings, &new), vec!["dexes"]);
+
+        settings = reload::merge(&settings, &new).unwrap();
+        assert_eq!(settings.minimum_profit_threshold, 7.5);
+        assert_eq!(settings.check_interval_seconds, 60);
+        assert_eq!(settings.dexes[0].router_address, parse_settings(VALID_SETTINGS).dexes[0].router_address);
+    }
+
+    #[test]
+    fn reload_rejects_changes_invalid_alongside_the_restart_only_settings() {
+        let mut settings = parse_settings(VALID_SETTINGS);
+        settings.confirmations = Some(3);
+        let mut new = parse_settings(VALID_SETTINGS);
+        new.price_confirmations = 2;
+        // Valid on its own, but `confirmations` stays in effect until a restart
+        assert_eq!(validate(&new), Ok(()));
+        assert!(reload::merge(&settings, &new).is_err());
+
+        new.display_decimals = 8;
+        assert_eq!(reload::restart_required(&settings, &new), vec!["confirmations", "display_decimals"]);
+    }
+
+    #[test]
+    fn accepts_valid_settings() {
+        assert_eq!(validate(&parse_settings(VALID_SETTINGS)), Ok(()));
+    }
+
+    #[test]
+    fn disabled_dexes_do_not_count() {
+        let mut settings = parse_settings(VALID_SETTINGS);
+        settings.dexes[1].enabled = false;
+        assert_eq!(settings.enabled_dexes().len(), 1);
+        assert_eq!(validate(&settings), Err(vec!["at least two enabled DEXes must be configured".to_string()]));
+    }
+
+    #[test]
+    fn reports_every_config_problem_at_once() {
+        let mut settings = parse_settings(VALID_SETTINGS);
+        settings.check_interval_seconds = 0;
+        settings.amount_in = Some(AmountIn::Single(0.0));
+        settings.minimum_profit_threshold = -1.0;
+        settings.dexes[1].router_address = settings.dexes[0].router_address;
+        settings.pairs[0].token_b = settings.pairs[0].token_a;
+        settings.max_concurrency = 0;
+
+        let problems = validate(&settings).unwrap_err();
+        assert_eq!(problems.len(), 6, "{:?}", problems);
+    }
+
+    #[test]
+    fn points_at_missing_output_directories() {
+        let mut settings = parse_settings(VALID_SETTINGS);
+        assert_eq!(check_paths(&settings), Ok(()));
+
+        settings.output_csv = Some("opportunities.csv".to_string());
+        settings.database_path = Some("no-such-dir/history.db".to_string());
+        let problems = check_paths(&settings).unwrap_err();
+        assert_eq!(problems.len(), 1, "{:?}", problems);
+        assert!(problems[0].contains("no-such-dir"), "{}", problems[0]);
+    }
+
+    #[test]
+    fn buys_on_dex_a_when_it_is_cheaper() {
+        let opportunity = detect(1000.0, 1010.0, 2.0, 1.0).unwrap();
+        assert_eq!(opportunity.buy_dex, "QuickSwap");
+        assert_eq!(opportunity.sell_dex, "SushiSwap");
+        assert_eq!(opportunity.gross_profit, 10.0);
+        assert_eq!(opportunity.net_profit, 8.0);
+    }
+
+    #[test]
+    fn buys_on_dex_b_when_it_is_cheaper() {
+        let opportunity = detect(1010.0, 1000.0, 2.0, 1.0).unwrap();
+        assert_eq!(opportunity.buy_dex, "SushiSwap");
+        assert_eq!(opportunity.sell_dex, "QuickSwap");
+        assert_eq!(opportunity.gross_profit, 10.0);
+        assert_eq!(opportunity.net_profit, 8.0);
+    }
+
+    #[test]
+    fn ignores_profit_below_threshold() {
+        assert_eq!(detect(1000.0, 1002.5, 2.0, 1.0), None);
+    }
+
+    #[test]
+    fn ignores_profit_exactly_at_threshold() {
+        assert_eq!(detect(1000.0, 1003.0, 2.0, 1.0), None);
+    }
+
+    #[test]
+    fn ignores_negative_gross_profit() {
+        let (buy, sell) = (dex("QuickSwap"), dex("SushiSwap"));
+        // The round trip returns less than it cost, e.g. because of pool fees
+        let thresholds = Thresholds {
+            minimum_profit: 1.0,
+            minimum_profit_percent: None,
+        };
+        let opportunity = check_opportunity(&weth_usdc(), 1.0, (&buy, 1000.0), (&sell, 1010.0), 995.0, costs(0.0, 0), thresholds);
+        assert_eq!(opportunity, None);
+    }
+
+    #[tokio::test]
+    async fn reads_the_gas_price_in_gwei() {
+        let (provider, mock) = Provider::mocked();
+        mock.push(U256::from(612_500_000_000u64)).unwrap();
+        assert_eq!(get_gas_price_gwei(&provider, Duration::from_secs(5)).await.unwrap(), 612.5);
+    }
+
+    #[test]
+    fn logs_the_native_wrapper_under_the_native_symbol() {
+        let (wmatic, usdc) = (Address::repeat_byte(1), Address::repeat_byte(2));
+        let symbols = HashMap::from([(wmatic, "WMATIC".to_string()), (usdc, "USDC".to_string())]);
+        let native = Some((wmatic, "MATIC"));
+        assert_eq!(token_symbol(None, wmatic, native, &symbols).as_deref(), Some("MATIC"));
+        assert_eq!(token_symbol(None, usdc, native, &symbols).as_deref(), Some("USDC"));
+        assert_eq!(token_symbol(None, wmatic, None, &symbols).as_deref(), Some("WMATIC"));
+        assert_eq!(token_symbol(Some("POL".to_string()), wmatic, native, &symbols).as_deref(), Some("POL"));
+    }
+
+    #[test]
+    fn searches_the_usual_places_for_the_settings_file() {
+        let candidates = config_candidates(None, Some(PathBuf::from("/home/bot/.config")), Some(PathBuf::from("/opt/bot")));
+        assert_eq!(candidates, ["./config/settings", "/home/bot/.config/arb-bot/settings", "/opt/bot/settings"]);
+        assert_eq!(config_candidates(None, None, None), ["./config/settings"]);
+        // An explicit path is the only one tried, so a typo in it isn't papered over
+        let explicit = config_candidates(Some("/etc/bot/staging"), Some(PathBuf::from("/home/bot/.config")), None);
+        assert_eq!(explicit, ["/etc/bot/staging"]);
+    }
+
+    #[test]
+    fn formats_a_spread_stream_line_per_pair() {
+        let timestamp = DateTime::parse_from_rfc3339("2026-10-14T12:00:00Z").unwrap().with_timezone(&Utc);
+        let line = output::spread_line(timestamp, "WETH/USDC", &[Some(3400.5), None, Some(3410.25)], 0.2867, 2);
+        assert_eq!(line, "2026-10-14T12:00:00Z,WETH/USDC,3400.50,,3410.25,0.29");
+    }
+
+    #[test]
+    fn names_configured_decimals_that_disagree_with_the_contract() {
+        let settings = parse_settings(VALID_SETTINGS);
+        let weth = settings.pairs[0].token_a;
+        let symbols = HashMap::from([(settings.pairs[0].token_b, "USDC".to_string())]);
+        assert!(decimals_mismatches(&settings, |token| Some(if token == weth { 18 } else { 6 }), &symbols).is_empty());
+        // Unreadable decimals fall back to the configured ones and aren't compared
+        assert!(decimals_mismatches(&settings, |_| None, &symbols).is_empty());
+        assert_eq!(
+            decimals_mismatches(&settings, |_| Some(18), &symbols),
+            vec!["WETH/USDC `decimals_b` is 6, but USDC (0x2791bca1f2de4661ed88a30c99a7a9449aa84174) has 18 decimals"]
+        );
+        assert_eq!(settings.on_decimals_mismatch, DecimalsMismatch::Warn);
+    }
+
+    #[test]
+    fn ranks_each_dex_combination_once_most_profitable_first() {
+        let (quickswap, sushiswap, uniswap) = (dex("QuickSwap"), dex("SushiSwap"), dex("Uniswap"));
+        let prices = [(&quickswap, 1010.0), (&sushiswap, 1000.0), (&uniswap, 1030.0)];
+        let ranking = rank_opportunities(&weth_usdc(), 1.0, &prices, costs(1.0, 0));
+        let routes: Vec<_> = ranking.iter().map(|trade| (trade.buy_dex.as_str(), trade.sell_dex.as_str())).collect();
+        assert_eq!(routes, [("SushiSwap", "Uniswap"), ("QuickSwap", "Uniswap"), ("SushiSwap", "QuickSwap")]);
+        assert!(ranking.windows(2).all(|pair| pair[0].net_profit >= pair[1].net_profit));
+        assert!(rank_opportunities(&weth_usdc(), 1.0, &prices[..1], costs(1.0, 0)).is_empty());
+    }
+
+    #[test]
+    fn deducts_transfer_taxes_and_skips_tokens_taxed_too_much() {
+        let mut settings = parse_settings(VALID_SETTINGS);
+        let pair = settings.pairs[0].clone();
+        settings.token_taxes = vec![TokenTax { token: pair.token_a, tax_bps: 50 }];
+        assert_eq!(validate(&settings), Ok(()));
+        // The round trip moves WETH into a pool and back out of one
+        let tax_bps = settings.route_tax_bps([&pair.path(false), &pair.path(true)]);
+        assert_eq!(tax_bps, 100);
+
+        let (buy, sell) = (dex("QuickSwap"), dex("SushiSwap"));
+        let trade = |costs| price_trade(&pair, 1.0, (&buy, 1000.0), (&sell, 1020.0), 1020.0, costs);
+        let (untaxed, taxed) = (trade(costs(2.0, 0)), trade(costs(2.0, 0).taxed(tax_bps)));
+        assert_eq!(taxed.transfer_tax, 10.0);
+        assert_eq!(untaxed.net_profit - taxed.net_profit, 10.0);
+
+        settings.max_tax_bps = Some(100);
+        exclude_taxed_tokens(&mut settings, &HashMap::new());
+        assert_eq!(settings.pairs.len(), 1);
+        settings.max_tax_bps = Some(25);
+        exclude_taxed_tokens(&mut settings, &HashMap::new());
+        assert!(settings.pairs.is_empty());
+    }
+
+    #[test]
+    fn a_pair_threshold_overrides_the_global_one() {
+        let settings = parse_settings(VALID_SETTINGS);
+        let (buy, sell) = (dex("QuickSwap"), dex("SushiSwap"));
+        // 8 USDC net clears the global 5.0 but not a pair's own 10.0
+        let check = |pair: &Pair| {
+            let thresholds = settings.thresholds_for(pair);
+            check_opportunity(pair, 1.0, (&buy, 1000.0), (&sell, 1010.0), 1010.0, costs(2.0, 0), thresholds)
+        };
+        assert!(check(&weth_usdc()).is_some());
+        assert_eq!(check(&Pair { minimum_profit_threshold: Some(10.0), ..weth_usdc() }), None);
+        assert!(check(&Pair { minimum_profit_threshold: Some(0.5), ..weth_usdc() }).is_some());
+    }
+
+    #[test]
+    fn reports_how_far_a_trade_fell_short_of_the_thresholds() {
+        let thresholds = Thresholds {
+            minimum_profit: 5.0,
+            minimum_profit_percent: None,
+        };
+        assert_eq!(thresholds.shortfall(2.0, 1000.0), 3.0);
+        // 1% of a 1000 USDC trade needs 10 USDC, more than the absolute minimum
+        let thresholds = Thresholds {
+            minimum_profit_percent: Some(1.0),
+            ..thresholds
+        };
+        assert_eq!(thresholds.shortfall(8.0, 1000.0), 2.0);
+    }
+
+    #[test]
+    fn requires_both_absolute_and_percent_thresholds() {
+        let (buy, sell) = (dex("QuickSwap"), dex("SushiSwap"));
+        // 8 USDC net on a 1000 USDC trade is 0.8%
+        let check = |minimum_profit_percent| {
+            let thresholds = Thresholds {
+                minimum_profit: 1.0,
+                minimum_profit_percent: Some(minimum_profit_percent),
+            };
+            check_opportunity(&weth_usdc(), 1.0, (&buy, 1000.0), (&sell, 1010.0), 1010.0, costs(2.0, 0), thresholds)
+        };
+        assert_eq!(check(0.5).unwrap().profit_percent, 0.8);
+        assert_eq!(check(1.0), None);
+    }
+
+    #[test]
+    fn thresholds_profit_in_basis_points_of_the_trade_value() {
+        let mut settings = parse_settings(VALID_SETTINGS);
+        settings.minimum_profit_bps = Some(50);
+        assert_eq!(validate(&settings), Ok(()));
+        let thresholds = settings.thresholds();
+        assert_eq!(thresholds.minimum_profit_percent, Some(0.5));
+
+        // 8 USDC net on a 1000 USDC trade is 80 bps, enough for 50 but not the 5 USDC minimum
+        let (buy, sell) = (dex("QuickSwap"), dex("SushiSwap"));
+        let check = |thresholds| {
+            check_opportunity(&weth_usdc(), 1.0, (&buy, 1000.0), (&sell, 1010.0), 1010.0, costs(2.0, 0), thresholds)
+        };
+        assert!(check(thresholds).is_some());
+        assert_eq!(check(Thresholds { minimum_profit: 10.0, ..thresholds }), None);
+        settings.minimum_profit_bps = Some(100);
+        assert_eq!(check(settings.thresholds()), None);
+
+        settings.minimum_profit_percent = Some(0.5);
+        assert!(validate(&settings).is_err());
+    }
+
+    #[test]
+    fn subtracts_fees_on_both_legs() {
+        let buy = Dex { fee_bps: 30, ..dex("QuickSwap") };
+        let sell = Dex { fee_bps: 10, ..dex("SushiSwap") };
+        let thresholds = Thresholds {
+            minimum_profit: 1.0,
+            minimum_profit_percent: None,
+        };
+        // 3.0 on the 1000 buy leg plus 1.01 on the 1010 sell leg
+        let opportunity = check_opportunity(&weth_usdc(), 1.0, (&buy, 1000.0), (&sell, 1010.0), 1010.0, costs(2.0, 0), thresholds).unwrap();
+        assert!((opportunity.fees - 4.01).abs() < 1e-9);
+        assert!((opportunity.net_profit - 3.99).abs() < 1e-9);
+    }
+
+    #[test]
+    fn deducts_slippage_from_the_sell_leg() {
+        let (buy, sell) = (dex("QuickSwap"), dex("SushiSwap"));
+        let thresholds = Thresholds {
+            minimum_profit: 1.0,
+            minimum_profit_percent: None,
+        };
+        // 50 bps of the 1010 received on the sell leg
+        let opportunity = check_opportunity(&weth_usdc(), 1.0, (&buy, 1000.0), (&sell, 1010.0), 1010.0, costs(2.0, 50), thresholds).unwrap();
+        assert!((opportunity.slippage - 5.05).abs() < 1e-9);
+        assert!((opportunity.net_profit - 2.95).abs() < 1e-9);
+        assert_eq!(check_opportunity(&weth_usdc(), 1.0, (&buy, 1000.0), (&sell, 1010.0), 1010.0, costs(2.0, 100), thresholds), None);
+    }
+
+    #[tokio::test]
+    async fn reports_held_opportunities_once_confirmed_unless_reorged() {
+        let (buy, sell) = (dex("QuickSwap"), dex("SushiSwap"));
+        let thresholds = Thresholds {
+            minimum_profit: 1.0,
+            minimum_profit_percent: None,
+        };
+        let at_block = |number| Opportunity {
+            block_number: Some(number),
+            ..check_opportunity(&weth_usdc(), 1.0, (&buy, 1000.0), (&sell, 1010.0), 1010.0, costs(2.0, 0), thresholds).unwrap()
+        };
+        let [hash_100, hash_101, orphaned] = [1u64, 2, 3].map(H256::from_low_u64_be);
+        let mut confirmations = confirm::Confirmations::new(2);
+        hold_for_confirmations(&mut confirmations, vec![at_block(100)], Some(hash_100));
+        hold_for_confirmations(&mut confirmations, vec![at_block(101)], Some(hash_101));
+        // Found without a known block, so it could never be confirmed
+        hold_for_confirmations(&mut confirmations, vec![at_block(101)], None);
+
+        let (provider, mock) = Provider::mocked();
+        mock.push(Block::<H256> { hash: Some(hash_100), ..Default::default() }).unwrap();
+        let reported = confirmed(&mut confirmations, 102, &provider, Duration::from_secs(1)).await;
+        assert_eq!(reported.len(), 1);
+        assert_eq!((reported[0].block_number, reported[0].confirmations), (Some(100), Some(2)));
+
+        // Block 101 was replaced by the time it had two blocks on top
+        mock.push(Block::<H256> { hash: Some(orphaned), ..Default::default() }).unwrap();
+        assert!(confirmed(&mut confirmations, 103, &provider, Duration::from_secs(1)).await.is_empty());
+        assert!(confirmed(&mut confirmations, 200, &provider, Duration::from_secs(1)).await.is_empty());
+    }
+
+    #[test]
+    fn identifies_an_opportunity_by_block_pair_dexes_and_size() {
+        let (buy, sell) = (dex("QuickSwap"), dex("SushiSwap"));
+        let thresholds = Thresholds {
+            minimum_profit: 1.0,
+            minimum_profit_percent: None,
+        };
+        let found = |block_number| Opportunity {
+            block_number,
+            ..check_opportunity(&weth_usdc(), 1.0, (&buy, 1000.0), (&sell, 1010.0), 1010.0, costs(2.0, 0), thresholds).unwrap()
+        };
+        let id = found(Some(100)).stable_id();
+        assert_eq!(id.len(), 16);
+        // Found again later in the same block, e.g. by a second endpoint
+        assert_eq!(found(Some(100)).stable_id(), id);
+        assert_ne!(found(Some(101)).stable_id(), id);
+        assert_ne!(Opportunity { amount_in: 2.0, ..found(Some(100)) }.stable_id(), id);
+    }
+
+    #[test]
+    fn labels_opportunities_with_the_pair_symbols() {
+        let (buy, sell) = (dex("QuickSwap"), dex("SushiSwap"));
+        let thresholds = Thresholds {
+            minimum_profit: 1.0,
+            minimum_profit_percent: None,
+        };
+        let check = |pair: &Pair| check_opportunity(pair, 1.0, (&buy, 1000.0), (&sell, 1010.0), 1010.0, costs(2.0, 0), thresholds).unwrap();
+
+        let opportunity = check(&weth_usdc());
+        assert_eq!((opportunity.symbol_a.as_str(), opportunity.symbol_b.as_str()), ("WETH", "USDC"));
+        let unnamed = Pair { symbol_a: None, symbol_b: None, ..weth_usdc() };
+        let opportunity = check(&unnamed);
+        assert_eq!((opportunity.symbol_a.as_str(), opportunity.symbol_b.as_str()), ("Token A", "Token B"));
+    }
+
+    #[test]
+    fn flags_cycles_that_return_more_than_threshold_plus_gas() {
+        let cycle = Cycle {
+            label: "WETH/USDC/WMATIC".to_string(),
+            tokens: [1u64, 2, 3].map(Address::from_low_u64_be).to_vec(),
+            decimals: 18,
+            symbol: None,
+        };
+        let thresholds = Thresholds {
+            minimum_profit: 0.01,
+            minimum_profit_percent: None,
+        };
+        let check = |amount_out| check_cycle(&cycle, &dex("QuickSwap"), 1.0, amount_out, costs(0.005, 0), thresholds);
+
+        assert_eq!(cycle.path(), [1u64, 2, 3, 1].map(Address::from_low_u64_be).to_vec());
+        assert!((check(1.02).unwrap().net_profit - 0.015).abs() < 1e-9);
+        assert_eq!(check(1.01), None);
+    }
+
+    #[test]
+    fn measures_price_impact_against_a_tiny_reference_quote() {
+        // 0.001 WETH fetches 2.0 USDC, but a full 1 WETH only 1900
+        assert!((price_impact_pct(1.0, 1900.0, 0.001, 2.0) - 5.0).abs() < 1e-9);
+        assert!(price_impact_pct(1.0, 2000.0, 0.001, 2.0).abs() < 1e-9);
+    }
+
+    #[test]
+    fn encodes_v3_paths_with_a_fee_between_tokens() {
+        let [a, b, c] = [1u64, 2, 3].map(Address::from_low_u64_be);
+        let encoded = v3_path(&[a, b, c], 3000);
+        assert_eq!(encoded.len(), 20 + 3 + 20 + 3 + 20);
+        assert_eq!(&encoded[..20], a.as_bytes());
+        assert_eq!(&encoded[20..23], &[0x00, 0x0b, 0xb8]);
+        assert_eq!(&encoded[43..46], &[0x00, 0x0b, 0xb8]);
+        assert_eq!(&encoded[46..], c.as_bytes());
+    }
+
+    #[test]
+    fn sweeps_evenly_spaced_trade_sizes() {
+        let mut settings = parse_settings(VALID_SETTINGS);
+        assert_eq!(settings.trade_sizes(), vec![1.0]);
+
+        settings.amount_sweep = Some(AmountSweep { min: 0.5, max: 2.0, steps: 4 });
+        assert_eq!(settings.trade_sizes(), vec![0.5, 1.0, 1.5, 2.0]);
+        settings.amount_sweep = Some(AmountSweep { min: 0.5, max: 2.0, steps: 1 });
+        assert_eq!(settings.trade_sizes(), vec![0.5]);
+    }
+
+    #[test]
+    fn checks_each_listed_trade_size() {
+        let settings = parse_settings(&VALID_SETTINGS.replace("amount_in = 1.0", "amount_in = [0.5, 1.0, 5.0]"));
+        assert_eq!(settings.trade_sizes(), vec![0.5, 1.0, 5.0]);
+        assert!(settings.reports_each_size());
+        assert_eq!(validate(&settings), Ok(()));
+        assert!(!parse_settings(VALID_SETTINGS).reports_each_size());
+
+        let mut settings = settings;
+        settings.amount_sweep = Some(AmountSweep { min: 0.5, max: 2.0, steps: 4 });
+        assert_eq!(validate(&settings), Err(vec!["`amount_in` can't be a list when `amount_sweep` is set".to_string()]));
+    }
+
+    #[tokio::test]
+    async fn converts_amount_in_usd_at_the_live_price() {
+        let mut settings = parse_settings(VALID_SETTINGS);
+        let both = Settings { amount_in_usd: Some(1000.0), ..settings.clone() };
+        assert_eq!(validate(&both), Err(vec!["set only one of `amount_in` and `amount_in_usd`".to_string()]));
+        settings.amount_in = None;
+        assert_eq!(validate(&settings), Err(vec!["one of `amount_in` and `amount_in_usd` must be set".to_string()]));
+        settings.amount_in_usd = Some(1000.0);
+        assert_eq!(validate(&settings), Ok(()));
+
+        let (provider, mock) = Provider::mocked();
+        let encoded = ethers::abi::encode(&[ethers::abi::Token::Array(vec![
+            ethers::abi::Token::Uint(U256::exp10(18)),
+            ethers::abi::Token::Uint(U256::from(2_500_000_000u64)),
+        ])]);
+        mock.push::<Bytes, _>(Bytes::from(encoded)).unwrap();
+        let markets =
+            registry::MarketRegistry::new(&settings.enabled_dexes(), Arc::new(provider), Default::default(), None);
+        let weth = weth_usdc().token_a;
+        // Priced at the block the pairs are quoted at, like everything else in a replay
+        let block = BlockId::from(50_000_000u64);
+        let amount_in = amount_in_from_usd(&markets, &settings, weth, 18, no_retries(), Some(block)).await.unwrap();
+        assert_eq!(amount_in, Some(0.4));
+        let router = IUniswapV2Router02::new(settings.enabled_dexes()[0].router_address, markets.client().clone());
+        let call = router.get_amounts_out(U256::exp10(18), vec![weth, settings.usdc]);
+        mock.assert_request("eth_call", (call.tx, block)).unwrap();
+        // USDC is worth a dollar without asking a DEX
+        let amount_in = amount_in_from_usd(&markets, &settings, settings.usdc, 6, no_retries(), None).await.unwrap();
+        assert_eq!(amount_in, Some(1000.0));
+    }
+
+    #[test]
+    fn profits_default_to_token_b_and_can_be_taken_in_token_a() {
+        assert_eq!(parse_settings(VALID_SETTINGS).pairs[0].profit_in, PairToken::B);
+
+        let mut settings = parse_settings(&VALID_SETTINGS.replace("decimals_b = 6", "decimals_b = 6\nprofit_in = \"a\""));
+        assert_eq!(settings.pairs[0].profit_in, PairToken::A);
+        assert_eq!(validate(&settings), Ok(()));
+
+        settings.profit_currency = Some(ProfitCurrency {
+            label: "USDC".to_string(),
+            token: settings.pairs[0].token_b,
+            decimals: 6,
+        });
+        assert_eq!(validate(&settings).unwrap_err().len(), 1);
+    }
+
+    #[test]
+    fn jitter_never_produces_a_non_positive_interval() {
+        let (interval, jitter) = (Duration::from_secs(1), Duration::from_secs(5));
+        for _ in 0..1000 {
+            let gap = jittered(interval, jitter);
+            assert!(gap >= MIN_INTERVAL && gap <= interval + jitter, "{:?}", gap);
+        }
+        assert_eq!(jittered(interval, Duration::ZERO), interval);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn ticks_every_interval_without_catching_up() {
+        let clock = Arc::new(clock::MockClock::new(1_000));
+        let interval = Duration::from_secs(30);
+        let (_pacing, updates) = tokio::sync::watch::channel(Pacing { interval, jitter: Duration::ZERO });
+        let ticks = polling_ticks(updates, clock.clone());
+        tokio::pin!(ticks);
+        let start = clock.now();
+
+        ticks.next().await;
+        assert_eq!(clock.now() - start, Duration::ZERO);
+        ticks.next().await;
+        assert_eq!(clock.now() - start, interval);
+        assert_eq!(clock.unix_seconds(), 1_030);
+
+        // A long backoff is followed by a single tick straight away, then the usual interval
+        clock.advance(Duration::from_secs(100)).await;
+        ticks.next().await;
+        assert_eq!(clock.now() - start, Duration::from_secs(130));
+        ticks.next().await;
+        assert_eq!(clock.now() - start, Duration::from_secs(160));
+
+        let heartbeat = watchdog::Heartbeat::new(clock.unix_seconds());
+        clock.advance(Duration::from_secs(61)).await;
+        assert!(heartbeat.is_stale(clock.unix_seconds(), Duration::from_secs(60)));
+    }
+
+    #[test]
+    fn builds_paths_with_intermediate_hops() {
+        let [a, b, c, d] = [1u64, 2, 3, 4].map(Address::from_low_u64_be);
+        let pair = |intermediate_tokens| Pair {
+            token_a: a,
+            token_b: b,
+            intermediate_tokens,
+            ..weth_usdc()
+        };
+
+        assert_eq!(pair(vec![]).path(false), vec![a, b]);
+        assert_eq!(pair(vec![c]).path(false), vec![a, c, b]);
+        assert_eq!(pair(vec![c, d]).path(false), vec![a, c, d, b]);
+        assert_eq!(pair(vec![c, d]).path(true), vec![b, d, c, a]);
+    }
+
+    #[test]
+    fn reads_the_last_amount_for_any_hop_count() {
+        let amounts = |n: u64| (1..=n).map(U256::from).collect::<Vec<_>>();
+        assert_eq!(final_amount(&amounts(2)).unwrap(), U256::from(2));
+        assert_eq!(final_amount(&amounts(3)).unwrap(), U256::from(3));
+        assert_eq!(final_amount(&amounts(4)).unwrap(), U256::from(4));
+        assert!(matches!(final_amount(&amounts(1)), Err(BotError::EmptyAmountsOut)));
+        assert!(matches!(final_amount(&[]), Err(BotError::EmptyAmountsOut)));
     }
 }
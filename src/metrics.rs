@@ -0,0 +1,117 @@
+use axum::extract::State;
+use axum::http::header::CONTENT_TYPE;
+use axum::response::IntoResponse;
+use axum::routing::get;
+use axum::Router;
+use eyre::Result;
+use prometheus::{Encoder, GaugeVec, Histogram, HistogramOpts, HistogramVec, IntCounterVec, Opts, Registry, TextEncoder};
+use std::net::SocketAddr;
+use std::sync::Arc;
+use std::time::Duration;
+use tracing::info;
+
+/// Counters and gauges exposed for Prometheus to scrape
+pub struct Metrics {
+    registry: Registry,
+    price_fetch_total: IntCounterVec,
+    price_fetch_errors_total: IntCounterVec,
+    opportunities_detected_total: IntCounterVec,
+    latest_price: GaugeVec,
+    latest_net_profit: GaugeVec,
+    rpc_rate_limit_wait_seconds: Histogram,
+    price_fetch_duration_seconds: HistogramVec,
+}
+
+impl Metrics {
+    pub fn new() -> Self {
+        let registry = Registry::new();
+        let counter = |name: &str, help: &str, labels: &[&str]| {
+            let counter = IntCounterVec::new(Opts::new(name, help), labels).expect("valid metric");
+            registry.register(Box::new(counter.clone())).expect("unique metric name");
+            counter
+        };
+        let price_fetch_total = counter("price_fetch_total", "DEX quotes requested", &["dex", "pair"]);
+        let price_fetch_errors_total = counter("price_fetch_errors_total", "DEX quotes that failed", &["dex", "pair"]);
+        let opportunities_detected_total =
+            counter("opportunities_detected_total", "Opportunities that cleared the threshold", &["pair"]);
+
+        let gauge = |name: &str, help: &str, labels: &[&str]| {
+            let gauge = GaugeVec::new(Opts::new(name, help), labels).expect("valid metric");
+            registry.register(Box::new(gauge.clone())).expect("unique metric name");
+            gauge
+        };
+        let latest_price = gauge("latest_price", "Latest Token B quote for `amount_in` Token A", &["dex", "pair"]);
+        let latest_net_profit = gauge("latest_net_profit", "Net profit of the latest opportunity, in Token B", &["pair"]);
+
+        let rpc_rate_limit_wait_seconds = Histogram::with_opts(HistogramOpts::new(
+            "rpc_rate_limit_wait_seconds",
+            "Time RPC requests spent waiting for the rate limiter, when they had to",
+        ))
+        .expect("valid metric");
+        registry.register(Box::new(rpc_rate_limit_wait_seconds.clone())).expect("unique metric name");
+        let price_fetch_duration_seconds = HistogramVec::new(
+            HistogramOpts::new("price_fetch_duration_seconds", "Time taken by each DEX quote, including retries"),
+            &["dex", "pair"],
+        )
+        .expect("valid metric");
+        registry.register(Box::new(price_fetch_duration_seconds.clone())).expect("unique metric name");
+
+        Metrics {
+            registry,
+            price_fetch_total,
+            price_fetch_errors_total,
+            opportunities_detected_total,
+            latest_price,
+            latest_net_profit,
+            rpc_rate_limit_wait_seconds,
+            price_fetch_duration_seconds,
+        }
+    }
+
+    /// Counts a quote and, when it succeeded, records it as the DEX's latest price
+    pub fn record_price(&self, dex: &str, pair: &str, price: Option<f64>) {
+        self.price_fetch_total.with_label_values(&[dex, pair]).inc();
+        match price {
+            Some(price) => self.latest_price.with_label_values(&[dex, pair]).set(price),
+            None => self.price_fetch_errors_total.with_label_values(&[dex, pair]).inc(),
+        }
+    }
+
+    /// Records how long a quote took, whether it succeeded or not
+    pub fn record_fetch_duration(&self, dex: &str, pair: &str, took: Duration) {
+        self.price_fetch_duration_seconds.with_label_values(&[dex, pair]).observe(took.as_secs_f64());
+    }
+
+    pub fn record_rate_limit_wait(&self, waited: Duration) {
+        self.rpc_rate_limit_wait_seconds.observe(waited.as_secs_f64());
+    }
+
+    #[cfg(test)]
+    pub fn rate_limit_waits(&self) -> u64 {
+        self.rpc_rate_limit_wait_seconds.get_sample_count()
+    }
+
+    pub fn record_opportunity(&self, pair: &str, net_profit: f64) {
+        self.opportunities_detected_total.with_label_values(&[pair]).inc();
+        self.latest_net_profit.with_label_values(&[pair]).set(net_profit);
+    }
+}
+
+/// Serves `GET /metrics` in the Prometheus text format until the listener fails
+pub async fn serve(addr: SocketAddr, metrics: Arc<Metrics>) -> Result<()> {
+    let app = Router::new().route("/metrics", get(scrape)).with_state(metrics);
+
+    let listener = tokio::net::TcpListener::bind(addr).await?;
+    info!("Serving metrics on http://{}/metrics", addr);
+    axum::serve(listener, app).await?;
+    Ok(())
+}
+
+async fn scrape(State(metrics): State<Arc<Metrics>>) -> impl IntoResponse {
+    let encoder = TextEncoder::new();
+    let mut body = Vec::new();
+    encoder
+        .encode(&metrics.registry.gather(), &mut body)
+        .expect("text encoding into a Vec can't fail");
+    ([(CONTENT_TYPE, encoder.format_type().to_string())], body)
+}
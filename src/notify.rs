@@ -0,0 +1,304 @@
+use crate::tier::Tier;
+use crate::Opportunity;
+use async_trait::async_trait;
+use eyre::Result;
+use reqwest::StatusCode;
+use serde::{Deserialize, Serialize};
+use std::sync::Arc;
+use std::time::Duration;
+use tracing::warn;
+
+/// Looks up an environment variable by name, so tests can supply their own
+pub type EnvLookup<'a> = &'a dyn Fn(&str) -> Option<String>;
+
+/// How long an alert may take to send before it's given up on
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// A client whose requests time out, so an unresponsive endpoint can't leave alerts pending
+fn http_client() -> reqwest::Result<reqwest::Client> {
+    reqwest::Client::builder().timeout(REQUEST_TIMEOUT).build()
+}
+
+/// One `[[notifiers]]` entry in the settings: an alert channel, and the least tier of
+/// opportunity sent to it
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct NotifierEntry {
+    #[serde(flatten)]
+    pub config: NotifierConfig,
+    #[serde(default)]
+    pub min_tier: Tier,
+}
+
+impl NotifierEntry {
+    /// The channel this entry describes, or `None` for a Telegram entry missing credentials
+    pub fn build(&self, decimals: usize, env: EnvLookup) -> Option<Channel> {
+        Some(Channel {
+            notifier: self.config.build(decimals, env)?,
+            min_tier: self.min_tier,
+        })
+    }
+
+    pub fn redacted(&self, placeholder: &str) -> Self {
+        NotifierEntry {
+            config: self.config.redacted(placeholder),
+            min_tier: self.min_tier,
+        }
+    }
+}
+
+/// An alert channel selected by `kind`
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "kind", rename_all = "lowercase")]
+pub enum NotifierConfig {
+    /// A Slack incoming webhook
+    Slack { webhook_url: String },
+    Discord { webhook_url: String },
+    /// Credentials default to `TELEGRAM_BOT_TOKEN` and `TELEGRAM_CHAT_ID`
+    Telegram {
+        bot_token: Option<String>,
+        chat_id: Option<String>,
+    },
+    /// Any endpoint accepting each opportunity as a JSON POST body
+    Webhook { url: String },
+}
+
+impl NotifierConfig {
+    /// The notifier this entry describes, or `None` for a Telegram entry missing credentials.
+    /// Amounts in text alerts are rounded to `decimals` places, and missing Telegram
+    /// credentials are looked up with `env`.
+    pub fn build(&self, decimals: usize, env: EnvLookup) -> Option<Arc<dyn Notifier>> {
+        Some(match self {
+            NotifierConfig::Slack { webhook_url } => Arc::new(Slack::new(webhook_url.clone(), decimals)),
+            NotifierConfig::Discord { webhook_url } => Arc::new(Discord::new(webhook_url.clone(), decimals)),
+            NotifierConfig::Telegram { bot_token, chat_id } => Arc::new(Telegram {
+                bot_token: bot_token.clone().or_else(|| env("TELEGRAM_BOT_TOKEN"))?,
+                chat_id: chat_id.clone().or_else(|| env("TELEGRAM_CHAT_ID"))?,
+                decimals,
+            }),
+            NotifierConfig::Webhook { url } => Arc::new(Webhook::new(url.clone())),
+        })
+    }
+
+    /// A copy with the URLs and tokens, which grant posting rights, replaced by `placeholder`
+    pub fn redacted(&self, placeholder: &str) -> Self {
+        let hidden = |value: &Option<String>| value.as_ref().map(|_| placeholder.to_string());
+        match self {
+            NotifierConfig::Slack { .. } => NotifierConfig::Slack { webhook_url: placeholder.to_string() },
+            NotifierConfig::Discord { .. } => NotifierConfig::Discord { webhook_url: placeholder.to_string() },
+            NotifierConfig::Telegram { bot_token, chat_id } => NotifierConfig::Telegram {
+                bot_token: hidden(bot_token),
+                chat_id: chat_id.clone(),
+            },
+            NotifierConfig::Webhook { .. } => NotifierConfig::Webhook { url: placeholder.to_string() },
+        }
+    }
+}
+
+/// A channel that opportunity alerts can be sent to
+#[async_trait]
+pub trait Notifier: Send + Sync {
+    /// Short name used in log messages
+    fn name(&self) -> &'static str;
+
+    async fn notify(&self, opportunity: &Opportunity) -> Result<()>;
+}
+
+/// A notifier that is only sent opportunities of at least `min_tier`
+#[derive(Clone)]
+pub struct Channel {
+    pub notifier: Arc<dyn Notifier>,
+    pub min_tier: Tier,
+}
+
+impl Channel {
+    /// A channel sent every opportunity
+    pub fn all(notifier: Arc<dyn Notifier>) -> Self {
+        Channel {
+            notifier,
+            min_tier: Tier::Info,
+        }
+    }
+}
+
+/// Fires every channel the opportunity's tier reaches in the background, so a slow or
+/// failing channel never delays the next tick or stops the other channels
+pub fn notify_all(channels: &[Channel], opportunity: &Opportunity) {
+    for channel in channels.iter().filter(|channel| opportunity.tier >= channel.min_tier) {
+        let notifier = channel.notifier.clone();
+        let opportunity = opportunity.clone();
+        tokio::spawn(async move {
+            if let Err(e) = notifier.notify(&opportunity).await {
+                warn!("Failed to send {} alert: {}", notifier.name(), e);
+            }
+        });
+    }
+}
+
+/// Telegram credentials, read from `TELEGRAM_BOT_TOKEN` and `TELEGRAM_CHAT_ID`
+#[derive(Debug, Clone)]
+pub struct Telegram {
+    bot_token: String,
+    chat_id: String,
+    decimals: usize,
+}
+
+impl Telegram {
+    /// Returns `None` when either variable is unset, which disables the notifier
+    pub fn from_env(decimals: usize, env: EnvLookup) -> Option<Self> {
+        Some(Telegram {
+            bot_token: env("TELEGRAM_BOT_TOKEN")?,
+            chat_id: env("TELEGRAM_CHAT_ID")?,
+            decimals,
+        })
+    }
+}
+
+#[async_trait]
+impl Notifier for Telegram {
+    fn name(&self) -> &'static str {
+        "Telegram"
+    }
+
+    async fn notify(&self, opportunity: &Opportunity) -> Result<()> {
+        notify_telegram(&self.bot_token, &self.chat_id, &format_message(opportunity, self.decimals)).await
+    }
+}
+
+/// Posts a plain-text message to a chat through the Telegram Bot API
+pub async fn notify_telegram(bot_token: &str, chat_id: &str, message: &str) -> Result<()> {
+    let url = format!("https://api.telegram.org/bot{}/sendMessage", bot_token);
+    http_client()?
+        .post(url)
+        .json(&serde_json::json!({ "chat_id": chat_id, "text": message }))
+        .send()
+        .await?
+        .error_for_status()?;
+    Ok(())
+}
+
+/// A Discord channel webhook
+#[derive(Debug, Clone)]
+pub struct Discord {
+    webhook_url: String,
+    decimals: usize,
+}
+
+impl Discord {
+    pub fn new(webhook_url: String, decimals: usize) -> Self {
+        Discord { webhook_url, decimals }
+    }
+}
+
+#[async_trait]
+impl Notifier for Discord {
+    fn name(&self) -> &'static str {
+        "Discord"
+    }
+
+    async fn notify(&self, opportunity: &Opportunity) -> Result<()> {
+        notify_discord(&self.webhook_url, opportunity, self.decimals).await
+    }
+}
+
+/// Posts an opportunity to a Discord webhook as an embed
+pub async fn notify_discord(webhook_url: &str, opportunity: &Opportunity, decimals: usize) -> Result<()> {
+    let payload = serde_json::json!({
+        "embeds": [{
+            "title": format!("Arbitrage opportunity on {}", opportunity.pair),
+            "timestamp": opportunity.timestamp.to_rfc3339(),
+            "fields": [
+                { "name": "Buy DEX", "value": opportunity.buy_dex, "inline": true },
+                { "name": "Sell DEX", "value": opportunity.sell_dex, "inline": true },
+                { "name": "Net Profit", "value": format!("{:.*}", decimals, opportunity.net_profit), "inline": true },
+                { "name": "Tier", "value": opportunity.tier.to_string(), "inline": true },
+            ],
+            "footer": { "text": opportunity.id },
+        }],
+    });
+    let response = http_client()?.post(webhook_url).json(&payload).send().await?;
+
+    // Discord rate-limits webhooks; dropping one alert is better than failing loudly
+    if response.status() == StatusCode::TOO_MANY_REQUESTS {
+        let retry_after = response
+            .headers()
+            .get("retry-after")
+            .and_then(|value| value.to_str().ok())
+            .unwrap_or("unknown");
+        warn!("Discord webhook rate-limited (retry after {}s), alert dropped", retry_after);
+        return Ok(());
+    }
+    response.error_for_status()?;
+    Ok(())
+}
+
+/// A Slack incoming webhook
+#[derive(Debug, Clone)]
+pub struct Slack {
+    webhook_url: String,
+    decimals: usize,
+}
+
+impl Slack {
+    pub fn new(webhook_url: String, decimals: usize) -> Self {
+        Slack { webhook_url, decimals }
+    }
+}
+
+#[async_trait]
+impl Notifier for Slack {
+    fn name(&self) -> &'static str {
+        "Slack"
+    }
+
+    async fn notify(&self, opportunity: &Opportunity) -> Result<()> {
+        http_client()?
+            .post(&self.webhook_url)
+            .json(&serde_json::json!({ "text": format_message(opportunity, self.decimals) }))
+            .send()
+            .await?
+            .error_for_status()?;
+        Ok(())
+    }
+}
+
+/// A generic webhook, sent the opportunity as JSON in the same shape as `GET /opportunities`
+#[derive(Debug, Clone)]
+pub struct Webhook {
+    url: String,
+}
+
+impl Webhook {
+    pub fn new(url: String) -> Self {
+        Webhook { url }
+    }
+}
+
+#[async_trait]
+impl Notifier for Webhook {
+    fn name(&self) -> &'static str {
+        "Webhook"
+    }
+
+    async fn notify(&self, opportunity: &Opportunity) -> Result<()> {
+        http_client()?.post(&self.url).json(opportunity).send().await?.error_for_status()?;
+        Ok(())
+    }
+}
+
+/// Renders an opportunity as a short human-readable alert, rounding amounts to `decimals` places
+fn format_message(opportunity: &Opportunity, decimals: usize) -> String {
+    format!(
+        "Arbitrage opportunity on {} ({})\nBuy on {}, sell on {}\nNet profit: {:.*} (gross {:.*}, gas {:.*})\nID: {}",
+        opportunity.pair,
+        opportunity.tier,
+        opportunity.buy_dex,
+        opportunity.sell_dex,
+        decimals,
+        opportunity.net_profit,
+        decimals,
+        opportunity.gross_profit,
+        decimals,
+        opportunity.gas_cost,
+        opportunity.id,
+    )
+}
@@ -0,0 +1,73 @@
+use crate::Opportunity;
+use chrono::{DateTime, SecondsFormat, Utc};
+use serde::{Deserialize, Serialize};
+use tracing::error;
+
+/// How results are written to stdout
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum OutputFormat {
+    /// Human-readable log lines
+    #[default]
+    Text,
+    /// One JSON object per line, with logs moved to stderr
+    Json,
+}
+
+/// What happened during one pass over every pair
+#[derive(Debug, Serialize)]
+pub struct TickResult {
+    pub timestamp: DateTime<Utc>,
+    pub tick: u64,
+    pub rpc: String,
+    pub block_number: Option<u64>,
+    /// Pairs and cycles that were priced successfully
+    pub scanned: usize,
+    pub errors: usize,
+    pub opportunities: usize,
+}
+
+impl TickResult {
+    pub fn new(tick: u64, rpc: &str) -> Self {
+        TickResult {
+            timestamp: Utc::now(),
+            tick,
+            rpc: rpc.to_string(),
+            block_number: None,
+            scanned: 0,
+            errors: 0,
+            opportunities: 0,
+        }
+    }
+}
+
+/// A line of JSON output, tagged with its kind so consumers can tell them apart
+#[derive(Serialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum Event<'a> {
+    Opportunity(&'a Opportunity),
+    Tick(&'a TickResult),
+}
+
+/// One line of the spread stream: `timestamp,pair,<price on each DEX>,spread_pct`, with
+/// an empty field for a DEX that couldn't be priced so columns stay aligned
+pub fn spread_line(
+    timestamp: DateTime<Utc>,
+    pair: &str,
+    prices: &[Option<f64>],
+    spread_pct: f64,
+    decimals: usize,
+) -> String {
+    let mut fields = vec![timestamp.to_rfc3339_opts(SecondsFormat::Secs, true), pair.to_string()];
+    fields.extend(prices.iter().map(|price| price.map(|price| format!("{:.*}", decimals, price)).unwrap_or_default()));
+    fields.push(format!("{:.*}", decimals, spread_pct));
+    fields.join(",")
+}
+
+/// Writes an event to stdout as a single line of JSON
+pub fn emit(event: Event) {
+    match serde_json::to_string(&event) {
+        Ok(line) => println!("{}", line),
+        Err(e) => error!("Error serializing output: {}", e),
+    }
+}
@@ -0,0 +1,74 @@
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use tracing::info;
+
+/// Whether scanning is paused, shared by the main loop, the SIGUSR1 handler and
+/// `POST /pause` / `POST /resume`
+#[derive(Debug, Clone, Default)]
+pub struct Pause(Arc<AtomicBool>);
+
+impl Pause {
+    pub fn is_paused(&self) -> bool {
+        self.0.load(Ordering::Relaxed)
+    }
+
+    /// Pauses or resumes, logging only when that changes anything
+    pub fn set(&self, paused: bool, by: &str) {
+        if self.0.swap(paused, Ordering::Relaxed) != paused {
+            log_change(paused, by);
+        }
+    }
+
+    /// Flips between paused and scanning, returning whether it is now paused
+    pub fn toggle(&self, by: &str) -> bool {
+        let paused = !self.0.fetch_xor(true, Ordering::Relaxed);
+        log_change(paused, by);
+        paused
+    }
+}
+
+fn log_change(paused: bool, by: &str) {
+    if paused {
+        info!("Scanning paused by {}.", by);
+    } else {
+        info!("Scanning resumed by {}.", by);
+    }
+}
+
+/// Toggles `pause` on every SIGUSR1, e.g. `kill -USR1 <pid>`, until the process exits
+#[cfg(unix)]
+pub fn listen_for_signal(pause: Pause) -> std::io::Result<()> {
+    let mut signals = tokio::signal::unix::signal(tokio::signal::unix::SignalKind::user_defined1())?;
+    tokio::spawn(async move {
+        while signals.recv().await.is_some() {
+            pause.toggle("SIGUSR1");
+        }
+    });
+    Ok(())
+}
+
+/// There is no SIGUSR1 here; `POST /pause` and `POST /resume` still work
+#[cfg(not(unix))]
+pub fn listen_for_signal(_: Pause) -> std::io::Result<()> {
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn toggles_and_sets_the_shared_state() {
+        let pause = Pause::default();
+        let shared = pause.clone();
+        assert!(!pause.is_paused());
+        assert!(pause.toggle("test"));
+        assert!(shared.is_paused());
+        assert!(!shared.toggle("test"));
+        pause.set(true, "test");
+        pause.set(true, "test");
+        assert!(shared.is_paused());
+        shared.set(false, "test");
+        assert!(!pause.is_paused());
+    }
+}
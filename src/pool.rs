@@ -0,0 +1,109 @@
+use async_trait::async_trait;
+use ethers::providers::JsonRpcClient;
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use std::fmt;
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+/// An RPC transport that hands each request to the next of several transports in turn,
+/// so concurrent price fetches spread over `provider_pool_size` connections instead of
+/// queueing behind one
+pub struct Pooled<P> {
+    transports: Vec<P>,
+    next: AtomicUsize,
+}
+
+impl<P> Pooled<P> {
+    pub fn new(transports: Vec<P>) -> Self {
+        assert!(!transports.is_empty(), "a pool needs at least one transport");
+        Pooled {
+            transports,
+            next: AtomicUsize::new(0),
+        }
+    }
+}
+
+impl<P: fmt::Debug> fmt::Debug for Pooled<P> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Pooled")
+            .field("first", &self.transports[0])
+            .field("size", &self.transports.len())
+            .finish()
+    }
+}
+
+#[async_trait]
+impl<P: JsonRpcClient> JsonRpcClient for Pooled<P> {
+    type Error = P::Error;
+
+    async fn request<T, R>(&self, method: &str, params: T) -> Result<R, Self::Error>
+    where
+        T: fmt::Debug + Serialize + Send + Sync,
+        R: DeserializeOwned + Send,
+    {
+        let i = self.next.fetch_add(1, Ordering::Relaxed) % self.transports.len();
+        self.transports[i].request(method, params).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ethers::providers::{MockError, MockProvider};
+    use ethers::types::U256;
+    use futures::future::join_all;
+    use std::sync::Arc;
+    use std::time::Duration;
+    use tokio::time::Instant;
+
+    /// Answers one request at a time, like a connection that doesn't pipeline
+    #[derive(Debug)]
+    struct OneAtATime {
+        busy: Arc<tokio::sync::Mutex<()>>,
+        mock: MockProvider,
+    }
+
+    #[async_trait]
+    impl JsonRpcClient for OneAtATime {
+        type Error = MockError;
+
+        async fn request<T, R>(&self, method: &str, params: T) -> Result<R, Self::Error>
+        where
+            T: fmt::Debug + Serialize + Send + Sync,
+            R: DeserializeOwned + Send,
+        {
+            let _busy = self.busy.lock().await;
+            tokio::time::sleep(Duration::from_millis(20)).await;
+            self.mock.request(method, params).await
+        }
+    }
+
+    /// How long the slowest of 8 concurrent requests took over a pool of `size`
+    async fn slowest_of_eight(size: usize) -> Duration {
+        let transports = (0..size)
+            .map(|_| {
+                let mock = MockProvider::new();
+                for _ in 0..8 {
+                    mock.push(U256::one()).unwrap();
+                }
+                OneAtATime { busy: Default::default(), mock }
+            })
+            .collect();
+        let pool = Pooled::new(transports);
+        let started = Instant::now();
+        let requests = (0..8).map(|_| async {
+            let _: U256 = pool.request("eth_call", ()).await.unwrap();
+            started.elapsed()
+        });
+        join_all(requests).await.into_iter().max().unwrap()
+    }
+
+    // Paused, the clock only moves by the simulated 20ms per request, however loaded the machine
+    #[tokio::test(start_paused = true)]
+    async fn spreads_concurrent_requests_to_cut_the_tail_latency() {
+        // One connection answers the 8th request after 160ms, four after 40ms
+        let (single, pooled) = (slowest_of_eight(1).await, slowest_of_eight(4).await);
+        assert_eq!(single, Duration::from_millis(160));
+        assert_eq!(pooled, Duration::from_millis(40));
+    }
+}
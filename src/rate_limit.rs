@@ -0,0 +1,104 @@
+use crate::metrics::Metrics;
+use async_trait::async_trait;
+use ethers::providers::{JsonRpcClient, PubsubClient};
+use ethers::types::U256;
+use governor::{DefaultDirectRateLimiter, Quota};
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use std::fmt;
+use std::num::NonZeroU32;
+use std::sync::Arc;
+use std::time::Instant;
+use tracing::debug;
+
+/// A token bucket refilled at `requests_per_second`, holding up to one second's worth
+pub fn limiter(requests_per_second: NonZeroU32) -> Arc<DefaultDirectRateLimiter> {
+    Arc::new(DefaultDirectRateLimiter::direct(Quota::per_second(requests_per_second)))
+}
+
+/// An RPC transport that waits for a token from a shared limiter before each request,
+/// so concurrent calls queue instead of tripping the provider's rate limit. Without a
+/// limiter requests pass straight through.
+pub struct RateLimited<P> {
+    inner: P,
+    limiter: Option<Arc<DefaultDirectRateLimiter>>,
+    metrics: Arc<Metrics>,
+}
+
+impl<P> RateLimited<P> {
+    pub fn new(inner: P, limiter: Option<Arc<DefaultDirectRateLimiter>>, metrics: Arc<Metrics>) -> Self {
+        RateLimited { inner, limiter, metrics }
+    }
+
+    /// Waits until the limiter allows another request, recording how long that took
+    async fn acquire(&self, method: &str) {
+        let Some(limiter) = &self.limiter else { return };
+        if limiter.check().is_ok() {
+            return;
+        }
+        let started = Instant::now();
+        limiter.until_ready().await;
+        let waited = started.elapsed();
+        self.metrics.record_rate_limit_wait(waited);
+        debug!("Rate limited {} for {}ms", method, waited.as_millis());
+    }
+}
+
+impl<P: fmt::Debug> fmt::Debug for RateLimited<P> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("RateLimited")
+            .field("inner", &self.inner)
+            .field("limited", &self.limiter.is_some())
+            .finish()
+    }
+}
+
+#[async_trait]
+impl<P: JsonRpcClient> JsonRpcClient for RateLimited<P> {
+    type Error = P::Error;
+
+    async fn request<T, R>(&self, method: &str, params: T) -> Result<R, Self::Error>
+    where
+        T: fmt::Debug + Serialize + Send + Sync,
+        R: DeserializeOwned + Send,
+    {
+        self.acquire(method).await;
+        self.inner.request(method, params).await
+    }
+}
+
+impl<P: PubsubClient> PubsubClient for RateLimited<P> {
+    type NotificationStream = P::NotificationStream;
+
+    fn subscribe<T: Into<U256>>(&self, id: T) -> Result<Self::NotificationStream, Self::Error> {
+        self.inner.subscribe(id)
+    }
+
+    fn unsubscribe<T: Into<U256>>(&self, id: T) -> Result<(), Self::Error> {
+        self.inner.unsubscribe(id)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ethers::providers::MockProvider;
+
+    #[tokio::test]
+    async fn waits_once_the_bucket_is_empty() {
+        let mock = MockProvider::new();
+        let metrics = Arc::new(Metrics::new());
+        let client = RateLimited::new(mock.clone(), Some(limiter(NonZeroU32::new(20).unwrap())), metrics.clone());
+
+        // The first 20 requests drain the bucket, the 21st waits about 50ms for a refill
+        for _ in 0..21 {
+            mock.push(U256::one()).unwrap();
+        }
+        let started = Instant::now();
+        for _ in 0..21 {
+            let _: U256 = client.request("eth_blockNumber", ()).await.unwrap();
+        }
+        assert!(started.elapsed().as_millis() >= 40);
+        assert_eq!(metrics.rate_limit_waits(), 1);
+    }
+}
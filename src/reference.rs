@@ -0,0 +1,147 @@
+use ethers::types::Address;
+use eyre::Result;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::sync::Mutex;
+use std::time::Duration;
+use tokio::time::Instant;
+
+/// The `[reference_price_source]` settings: where to fetch off-chain prices from
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ReferencePriceSource {
+    #[serde(default = "default_api_url")]
+    pub api_url: String,
+    /// CoinGecko's id for the chain the token addresses are on
+    #[serde(default = "default_platform")]
+    pub platform: String,
+    /// Sent as `x-cg-demo-api-key`, which raises the free tier's rate limit
+    pub api_key: Option<String>,
+    /// Reuse a fetched price for this long, to stay within CoinGecko's rate limits
+    #[serde(default = "default_ttl_seconds")]
+    pub ttl_seconds: u64,
+    /// Warn when a DEX's price is further than this from the reference
+    pub max_deviation_pct: Option<f64>,
+}
+
+fn default_api_url() -> String {
+    "https://api.coingecko.com/api/v3".to_string()
+}
+
+fn default_platform() -> String {
+    "polygon-pos".to_string()
+}
+
+fn default_ttl_seconds() -> u64 {
+    60
+}
+
+/// Reference prices for pairs, from the USD prices of their tokens, cached per token
+pub struct ReferencePrices {
+    source: ReferencePriceSource,
+    http: reqwest::Client,
+    cache: Mutex<HashMap<Address, (Instant, f64)>>,
+}
+
+impl ReferencePrices {
+    /// A request taking longer than `timeout` fails, so a stalled API can't hold up a tick
+    pub fn new(source: ReferencePriceSource, timeout: Duration) -> Result<Self> {
+        Ok(ReferencePrices {
+            source,
+            http: reqwest::Client::builder().timeout(timeout).build()?,
+            cache: Mutex::new(HashMap::new()),
+        })
+    }
+
+    pub fn max_deviation_pct(&self) -> Option<f64> {
+        self.source.max_deviation_pct
+    }
+
+    /// How much Token B one Token A is worth off-chain. Only tokens missing from the
+    /// cache or older than the TTL are fetched.
+    pub async fn price(&self, token_a: Address, token_b: Address, now: Instant) -> Result<f64> {
+        let missing: Vec<Address> = [token_a, token_b]
+            .into_iter()
+            .filter(|&token| self.cached(token, now).is_none())
+            .collect();
+        if !missing.is_empty() {
+            let fetched = fetch_reference_price(&self.http, &self.source, &missing).await?;
+            let mut cache = self.cache.lock().unwrap();
+            for (token, price) in fetched {
+                cache.insert(token, (now, price));
+            }
+        }
+        let usd_price = |token| {
+            self.cached(token, now)
+                .ok_or_else(|| eyre::eyre!("CoinGecko has no USD price for {:?}", token))
+        };
+        Ok(usd_price(token_a)? / usd_price(token_b)?)
+    }
+
+    fn cached(&self, token: Address, now: Instant) -> Option<f64> {
+        let ttl = Duration::from_secs(self.source.ttl_seconds);
+        match self.cache.lock().unwrap().get(&token) {
+            Some(&(fetched, price)) if now.duration_since(fetched) < ttl => Some(price),
+            _ => None,
+        }
+    }
+}
+
+/// Fetches the USD price of each token from CoinGecko's `simple/token_price` endpoint.
+/// Tokens CoinGecko doesn't list are left out of the result.
+pub async fn fetch_reference_price(
+    http: &reqwest::Client,
+    source: &ReferencePriceSource,
+    tokens: &[Address],
+) -> Result<HashMap<Address, f64>> {
+    let addresses: Vec<String> = tokens.iter().map(|token| format!("{:?}", token)).collect();
+    let url = format!("{}/simple/token_price/{}", source.api_url.trim_end_matches('/'), source.platform);
+    let mut request = http
+        .get(url)
+        .query(&[("contract_addresses", addresses.join(",")), ("vs_currencies", "usd".to_string())]);
+    if let Some(api_key) = &source.api_key {
+        request = request.header("x-cg-demo-api-key", api_key);
+    }
+    let body: HashMap<String, HashMap<String, f64>> = request.send().await?.error_for_status()?.json().await?;
+    Ok(usd_prices(body))
+}
+
+/// Picks the USD prices out of a response keyed by lowercase contract address
+fn usd_prices(body: HashMap<String, HashMap<String, f64>>) -> HashMap<Address, f64> {
+    body.into_iter()
+        .filter_map(|(address, prices)| Some((address.parse().ok()?, *prices.get("usd")?)))
+        .collect()
+}
+
+/// How far `price` is from `reference`, in percent of the reference
+pub fn deviation_pct(price: f64, reference: f64) -> f64 {
+    (price - reference) / reference * 100.0
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn reads_usd_prices_and_expires_them_after_the_ttl() {
+        let body = serde_json::from_str(
+            r#"{
+                "0x7ceb23fd6bc0add59e62ac25578270cff1b9f619": {"usd": 3400.5},
+                "0x2791bca1f2de4661ed88a30c99a7a9449aa84174": {"usd": 1.0},
+                "0x0000000000000000000000000000000000000001": {}
+            }"#,
+        )
+        .unwrap();
+        let prices = usd_prices(body);
+        let weth: Address = "0x7ceb23fd6bc0add59e62ac25578270cff1b9f619".parse().unwrap();
+        assert_eq!(prices.len(), 2);
+        assert_eq!(prices[&weth], 3400.5);
+
+        let source: ReferencePriceSource = serde_json::from_str(r#"{"ttl_seconds": 60}"#).unwrap();
+        let reference = ReferencePrices::new(source, Duration::from_secs(5)).unwrap();
+        let start = Instant::now();
+        reference.cache.lock().unwrap().insert(weth, (start, 3400.5));
+        assert_eq!(reference.cached(weth, start + Duration::from_secs(59)), Some(3400.5));
+        assert_eq!(reference.cached(weth, start + Duration::from_secs(60)), None);
+        assert_eq!(deviation_pct(3434.0, 3400.0), 1.0);
+    }
+}
@@ -0,0 +1,160 @@
+use crate::rpc_log::{Recorded, RpcLog};
+use crate::source::PriceSource;
+use crate::{Dex, DexKind, Quoter, Settings, IERC20};
+use ethers::providers::Middleware;
+use ethers::types::Address;
+use std::collections::{HashMap, HashSet};
+use std::sync::Arc;
+use tracing::{debug, warn};
+
+/// Decimals of every token the bot trades, by token address
+pub type TokenDecimals = HashMap<Address, u32>;
+
+/// The contract bindings and token metadata for one RPC endpoint. Built once at
+/// startup so the main loop only ever looks things up.
+pub struct MarketRegistry<M> {
+    client: Arc<M>,
+    /// Every configured DEX with its quoting contract, in config order
+    contracts: Vec<(Dex, Box<dyn PriceSource>)>,
+    /// Position in `contracts` by router address
+    by_router: HashMap<Address, usize>,
+    decimals: Arc<TokenDecimals>,
+}
+
+impl<M: Middleware + 'static> MarketRegistry<M> {
+    /// With an `rpc_log`, every V2 router's quotes are recorded in it
+    pub fn new(dexes: &[Dex], client: Arc<M>, decimals: Arc<TokenDecimals>, rpc_log: Option<Arc<RpcLog>>) -> Self {
+        let contracts: Vec<(Dex, Box<dyn PriceSource>)> = dexes
+            .iter()
+            .map(|dex| {
+                let source: Box<dyn PriceSource> = match (&rpc_log, dex.kind) {
+                    (Some(log), DexKind::V2) => Box::new(Recorded::new(dex, client.clone(), log.clone())),
+                    _ => Box::new(Quoter::new(dex, client.clone())),
+                };
+                (dex.clone(), source)
+            })
+            .collect();
+        let by_router = contracts.iter().enumerate().map(|(i, (dex, _))| (dex.router_address, i)).collect();
+        MarketRegistry {
+            client,
+            contracts,
+            by_router,
+            decimals,
+        }
+    }
+
+    /// A registry quoting through the given sources rather than the DEXes' contracts
+    #[cfg(test)]
+    pub fn with_sources(contracts: Vec<(Dex, Box<dyn PriceSource>)>, client: Arc<M>) -> Self {
+        let by_router = contracts.iter().enumerate().map(|(i, (dex, _))| (dex.router_address, i)).collect();
+        MarketRegistry {
+            client,
+            contracts,
+            by_router,
+            decimals: Default::default(),
+        }
+    }
+
+    pub fn client(&self) -> &Arc<M> {
+        &self.client
+    }
+
+    pub fn contracts(&self) -> &[(Dex, Box<dyn PriceSource>)] {
+        &self.contracts
+    }
+
+    /// The quoting contract of the DEX with this router (or V3 quoter) address
+    pub fn quoter(&self, router_address: Address) -> Option<&dyn PriceSource> {
+        self.by_router.get(&router_address).map(|&i| self.contracts[i].1.as_ref())
+    }
+
+    pub fn decimals(&self, token: Address) -> Option<u32> {
+        self.decimals.get(&token).copied()
+    }
+}
+
+/// Reads the decimals of every configured token from its ERC20 contract, once per
+/// distinct token, falling back to the configured value for any that can't be read
+pub async fn token_decimals<M: Middleware>(settings: &Settings, client: Arc<M>) -> TokenDecimals {
+    let configured = settings
+        .pairs
+        .iter()
+        .flat_map(|pair| [(pair.token_a, pair.decimals_a), (pair.token_b, pair.decimals_b)])
+        .chain(settings.cycles.iter().map(|cycle| (cycle.tokens[0], cycle.decimals)))
+        .chain(settings.profit_currency.iter().map(|currency| (currency.token, currency.decimals)));
+
+    let mut decimals = TokenDecimals::new();
+    for (token, fallback) in configured {
+        if decimals.contains_key(&token) {
+            continue;
+        }
+        let resolved = match IERC20::new(token, client.clone()).decimals().call().await {
+            Ok(decimals) => u32::from(decimals),
+            Err(e) => {
+                warn!("Could not read decimals for {:?}, using configured {}: {}", token, fallback, e);
+                fallback
+            }
+        };
+        decimals.insert(token, resolved);
+    }
+    decimals
+}
+
+/// Reads the symbol of every configured token that has none set from its ERC20
+/// contract, once per distinct token. Tokens whose symbol can't be read are left out.
+pub async fn token_symbols<M: Middleware>(settings: &Settings, client: Arc<M>) -> HashMap<Address, String> {
+    let unnamed = settings
+        .pairs
+        .iter()
+        .flat_map(|pair| [(pair.token_a, &pair.symbol_a), (pair.token_b, &pair.symbol_b)])
+        .chain(settings.cycles.iter().map(|cycle| (cycle.tokens[0], &cycle.symbol)))
+        .filter(|(_, configured)| configured.is_none())
+        .map(|(token, _)| token);
+
+    let mut symbols = HashMap::new();
+    for token in unnamed {
+        if symbols.contains_key(&token) {
+            continue;
+        }
+        match IERC20::new(token, client.clone()).symbol().call().await {
+            Ok(symbol) => {
+                symbols.insert(token, symbol);
+            }
+            Err(e) => warn!("Could not read the symbol of {:?}: {}", token, e),
+        }
+    }
+    symbols
+}
+
+/// Describes every configured router and token with no contract deployed at its address,
+/// e.g. a copy-paste mistake or an address from another chain. Addresses whose code
+/// can't be read are only warned about, leaving an unreachable node to fail later.
+pub async fn missing_contracts<M: Middleware>(settings: &Settings, dexes: &[Dex], client: &M) -> Vec<String> {
+    let routers = dexes.iter().map(|dex| (dex.router_address, format!("router of {}", dex.name)));
+    let pair_tokens = settings.pairs.iter().flat_map(|pair| {
+        [pair.token_a, pair.token_b]
+            .into_iter()
+            .chain(pair.intermediate_tokens.iter().copied())
+            .map(move |token| (token, format!("token in pair {}", pair.label)))
+    });
+    let cycle_tokens = settings.cycles.iter().flat_map(|cycle| {
+        cycle.tokens.iter().map(move |&token| (token, format!("token in cycle {}", cycle.label)))
+    });
+    let others = [(settings.wmatic, "`wmatic`".to_string()), (settings.usdc, "`usdc`".to_string())]
+        .into_iter()
+        .chain(settings.profit_currency.iter().map(|currency| (currency.token, "`profit_currency` token".to_string())));
+
+    let mut checked = HashSet::new();
+    let mut missing = Vec::new();
+    for (address, role) in routers.chain(pair_tokens).chain(cycle_tokens).chain(others) {
+        if !checked.insert(address) {
+            continue;
+        }
+        match client.get_code(address, None).await {
+            Ok(code) if code.is_empty() => missing.push(format!("{:?} ({}) has no contract code", address, role)),
+            Ok(_) => debug!("Found contract code at {:?} ({})", address, role),
+            Err(e) => warn!("Could not check for contract code at {:?} ({}): {}", address, role, e),
+        }
+    }
+    missing
+}
@@ -0,0 +1,206 @@
+use crate::{load_settings, validate, Pacing, Settings};
+use eyre::Result;
+use ::notify::{EventKind, RecommendedWatcher, RecursiveMode, Watcher};
+use std::ffi::OsString;
+use std::path::Path;
+use tokio::sync::{mpsc, watch};
+use tracing::{info, warn};
+
+/// Reloads the settings file whenever it changes. Only settings read afresh every tick
+/// are applied; the rest need the contracts, servers or alerting rebuilt.
+pub struct Reloader {
+    path: String,
+    interval_override: Option<u64>,
+    /// The settings as last loaded, before any command-line overrides or startup
+    /// adjustments, to tell what changed in the file
+    loaded: Settings,
+    pacing: watch::Sender<Pacing>,
+    changes: mpsc::UnboundedReceiver<()>,
+    _watcher: RecommendedWatcher,
+}
+
+impl Reloader {
+    /// Watches the file behind `path` (the extension may be omitted). The directory is
+    /// watched rather than the file, so editors that replace the file are still seen.
+    pub fn watch(
+        path: &str,
+        interval_override: Option<u64>,
+        loaded: Settings,
+        pacing: watch::Sender<Pacing>,
+    ) -> Result<Self> {
+        let path_ref = Path::new(path);
+        let stem = path_ref.file_stem().map(OsString::from);
+        let dir = match path_ref.parent() {
+            Some(dir) if !dir.as_os_str().is_empty() => dir,
+            _ => Path::new("."),
+        };
+
+        let (sender, changes) = mpsc::unbounded_channel();
+        let mut watcher = ::notify::recommended_watcher(move |event: ::notify::Result<::notify::Event>| {
+            let Ok(event) = event else { return };
+            let ours = event.paths.iter().any(|changed| changed.file_stem().map(OsString::from) == stem);
+            if ours && !matches!(event.kind, EventKind::Access(_)) {
+                let _ = sender.send(());
+            }
+        })?;
+        watcher.watch(dir, RecursiveMode::NonRecursive)?;
+
+        Ok(Reloader {
+            path: path.to_string(),
+            interval_override,
+            loaded,
+            pacing,
+            changes,
+            _watcher: watcher,
+        })
+    }
+
+    /// Applies the file's latest contents to `settings` if it changed since the last call.
+    /// Returns whether anything was applied; invalid files are rejected with a warning.
+    pub fn reload(&mut self, settings: &mut Settings) -> bool {
+        // Saving a file often fires several events; one reload covers them all
+        let mut changed = false;
+        while self.changes.try_recv().is_ok() {
+            changed = true;
+        }
+        if !changed {
+            return false;
+        }
+
+        let mut new = match load_settings(&self.path, false) {
+            Ok(new) => new,
+            Err(e) => {
+                warn!("Not reloading settings: {}", e);
+                return false;
+            }
+        };
+        if let Some(interval) = self.interval_override {
+            new.check_interval_seconds = interval;
+        }
+        if let Err(problems) = validate(&new) {
+            warn!("Not reloading settings, keeping the current ones: {}", problems.join("; "));
+            return false;
+        }
+
+        let merged = match merge(settings, &new) {
+            Ok(merged) => merged,
+            Err(problems) => {
+                warn!(
+                    "Not reloading settings, they conflict with ones that need a restart: {}",
+                    problems.join("; ")
+                );
+                return false;
+            }
+        };
+        let restart_required = restart_required(&self.loaded, &new);
+        if !restart_required.is_empty() {
+            warn!("Changes to {} need a restart and were not applied", restart_required.join(", "));
+        }
+        *settings = merged;
+        self.pacing.send_replace(settings.pacing());
+        self.loaded = new;
+        info!("Reloaded settings from {}.", self.path);
+        true
+    }
+}
+
+/// The running `settings` with `new`'s hot-reloadable ones applied, as long as the result
+/// is still valid: `new` can be valid on its own and still clash with a setting that
+/// keeps its old value until a restart
+pub fn merge(settings: &Settings, new: &Settings) -> Result<Settings, Vec<String>> {
+    let mut merged = settings.clone();
+    apply(&mut merged, new);
+    validate(&merged).map(|()| merged)
+}
+
+/// Copies every setting that takes effect from the next tick
+fn apply(settings: &mut Settings, new: &Settings) {
+    settings.check_interval_seconds = new.check_interval_seconds;
+    settings.interval_jitter_seconds = new.interval_jitter_seconds;
+    settings.minimum_profit_threshold = new.minimum_profit_threshold;
+    settings.minimum_profit_percent = new.minimum_profit_percent;
+    settings.minimum_profit_bps = new.minimum_profit_bps;
+    settings.price_confirmations = new.price_confirmations;
+    settings.amount_in = new.amount_in.clone();
+    settings.amount_in_usd = new.amount_in_usd;
+    settings.tiers = new.tiers;
+    settings.amount_sweep = new.amount_sweep;
+    settings.simulated_gas_cost_usdc = new.simulated_gas_cost_usdc;
+    settings.gas_units = new.gas_units;
+    settings.congestion_factor = new.congestion_factor;
+    settings.max_gas_price_gwei = new.max_gas_price_gwei;
+    settings.congestion_threshold = new.congestion_threshold;
+    settings.max_retries = new.max_retries;
+    settings.retry_base_ms = new.retry_base_ms;
+    settings.rpc_timeout_seconds = new.rpc_timeout_seconds;
+    settings.max_concurrency = new.max_concurrency;
+    settings.max_price_impact_pct = new.max_price_impact_pct;
+    settings.min_liquidity_usdc = new.min_liquidity_usdc;
+    settings.max_pool_idle_seconds = new.max_pool_idle_seconds;
+    settings.slippage_bps = new.slippage_bps;
+    settings.simulate_swap = new.simulate_swap;
+    settings.simulate_swap_from = new.simulate_swap_from;
+    settings.summary_every_n_ticks = new.summary_every_n_ticks;
+    settings.top_near_misses = new.top_near_misses;
+    settings.max_recent_opportunities = new.max_recent_opportunities;
+    settings.fallback_matic_price_usdc = new.fallback_matic_price_usdc;
+}
+
+/// The settings that differ between `old` and `new` but are only read at startup
+pub fn restart_required(old: &Settings, new: &Settings) -> Vec<&'static str> {
+    let mut changed = Vec::new();
+    macro_rules! compare {
+        ($($field:ident),*) => {
+            $(if old.$field != new.$field {
+                changed.push(stringify!($field));
+            })*
+        };
+    }
+    compare!(
+        run_once,
+        at_block,
+        record_rpc,
+        max_iterations,
+        max_runtime_seconds,
+        warmup_ticks,
+        confirmations,
+        rpc_urls,
+        max_rpc_requests_per_second,
+        provider_pool_size,
+        failover_after_failures,
+        max_consecutive_failures,
+        breaker_cooldown_seconds,
+        watchdog_timeout_seconds,
+        watchdog_exit,
+        staleness_threshold_seconds,
+        spread_window,
+        output_format,
+        spread_stream,
+        sinks,
+        output_csv,
+        output_dir,
+        database_path,
+        paper_ledger_path,
+        http_port,
+        metrics_port,
+        bind_address,
+        discord_webhook_url,
+        notifiers,
+        token_taxes,
+        max_tax_bps,
+        on_decimals_mismatch,
+        alert_cooldown_seconds,
+        display_decimals,
+        execution_mode,
+        wmatic,
+        native_symbol,
+        usdc,
+        profit_currency,
+        reference_price_source,
+        pairs,
+        triangular,
+        cycles,
+        dexes
+    );
+    changed
+}
@@ -0,0 +1,131 @@
+use crate::error::BotError;
+use crate::source::PriceSource;
+use crate::{at_block, final_amount, Dex, IUniswapV2Router02};
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use ethers::providers::Middleware;
+use ethers::types::{Address, BlockId, BlockNumber, U256};
+use serde::{Deserialize, Serialize};
+use std::fs::{File, OpenOptions};
+use std::io::Write;
+use std::sync::{Arc, Mutex};
+use tracing::warn;
+
+/// The JSONL file `--record-rpc` appends every raw `getAmountsOut` call to, so a
+/// suspicious opportunity can be replayed and diffed against what the node returned
+pub struct RpcLog {
+    file: Mutex<File>,
+}
+
+/// One `getAmountsOut` call and the node's answer. Amounts are decimal strings of raw
+/// token units, so nothing is lost to rounding.
+#[derive(Debug, PartialEq, Serialize, Deserialize)]
+pub struct Entry {
+    pub timestamp: DateTime<Utc>,
+    pub dex: String,
+    pub router: Address,
+    pub amount_in: String,
+    pub path: Vec<Address>,
+    /// Unset if the call failed
+    pub amounts_out: Option<Vec<String>>,
+    pub error: Option<String>,
+    /// Unset for calls against the latest block
+    pub block: Option<u64>,
+}
+
+impl RpcLog {
+    pub fn create(path: &str) -> std::io::Result<Self> {
+        let file = OpenOptions::new().create(true).append(true).open(path)?;
+        Ok(RpcLog { file: Mutex::new(file) })
+    }
+
+    /// Appends one entry. A failed write is logged rather than failing the quote.
+    fn record(&self, entry: &Entry) {
+        let line = serde_json::to_string(entry).expect("entries always serialize");
+        if let Err(e) = writeln!(self.file.lock().unwrap(), "{}", line) {
+            warn!("Error writing to the RPC log: {}", e);
+        }
+    }
+}
+
+/// A V2 router quoted like `Quoter::V2`, recording each call in an `RpcLog`
+pub struct Recorded<M> {
+    dex: String,
+    router: IUniswapV2Router02<M>,
+    log: Arc<RpcLog>,
+}
+
+impl<M: Middleware> Recorded<M> {
+    pub fn new(dex: &Dex, client: Arc<M>, log: Arc<RpcLog>) -> Self {
+        Recorded {
+            dex: dex.name.clone(),
+            router: IUniswapV2Router02::new(dex.router_address, client),
+            log,
+        }
+    }
+}
+
+#[async_trait]
+impl<M: Middleware + 'static> PriceSource for Recorded<M> {
+    async fn amount_out(&self, amount_in: U256, path: &[Address], block: Option<BlockId>) -> Result<U256, BotError> {
+        let result = at_block(self.router.get_amounts_out(amount_in, path.to_vec()), block).call().await;
+        self.log.record(&Entry {
+            timestamp: Utc::now(),
+            dex: self.dex.clone(),
+            router: self.router.address(),
+            amount_in: amount_in.to_string(),
+            path: path.to_vec(),
+            amounts_out: result.as_ref().ok().map(|amounts| amounts.iter().map(U256::to_string).collect()),
+            error: result.as_ref().err().map(ToString::to_string),
+            block: block.and_then(|block| match block {
+                BlockId::Number(BlockNumber::Number(number)) => Some(number.as_u64()),
+                _ => None,
+            }),
+        });
+        final_amount(&result?)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::DexKind;
+    use ethers::providers::Provider;
+    use ethers::types::Bytes;
+
+    #[tokio::test]
+    async fn records_each_call_with_its_raw_amounts() {
+        let path = std::env::temp_dir().join(format!("rpc-log-{}.jsonl", std::process::id()));
+        let _ = std::fs::remove_file(&path);
+        let log = Arc::new(RpcLog::create(path.to_str().unwrap()).unwrap());
+
+        let (provider, mock) = Provider::mocked();
+        let amounts = [U256::exp10(18), U256::from(3_400_000_000u64)];
+        let encoded = ethers::abi::encode(&[ethers::abi::Token::Array(
+            amounts.iter().map(|amount| ethers::abi::Token::Uint(*amount)).collect(),
+        )]);
+        mock.push::<Bytes, _>(Bytes::from(encoded)).unwrap();
+        let dex = Dex {
+            name: "QuickSwap".to_string(),
+            kind: DexKind::V2,
+            router_address: Address::repeat_byte(1),
+            fee_tier: None,
+            fee_bps: 30,
+            enabled: true,
+        };
+        let source = Recorded::new(&dex, Arc::new(provider), log);
+        let tokens = vec![Address::repeat_byte(2), Address::repeat_byte(3)];
+        let block = BlockId::from(50_000_000u64);
+        assert_eq!(source.amount_out(U256::exp10(18), &tokens, Some(block)).await.unwrap(), amounts[1]);
+
+        let contents = std::fs::read_to_string(&path).unwrap();
+        let _ = std::fs::remove_file(&path);
+        let entries: Vec<Entry> = contents.lines().map(|line| serde_json::from_str(line).unwrap()).collect();
+        assert_eq!(entries.len(), 1);
+        assert_eq!(entries[0].dex, "QuickSwap");
+        assert_eq!(entries[0].amount_in, "1000000000000000000");
+        assert_eq!(entries[0].path, tokens);
+        assert_eq!(entries[0].amounts_out, Some(vec!["1000000000000000000".to_string(), "3400000000".to_string()]));
+        assert_eq!((entries[0].error.as_deref(), entries[0].block), (None, Some(50_000_000)));
+    }
+}
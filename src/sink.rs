@@ -0,0 +1,203 @@
+use crate::daily_csv::DailyCsv;
+use crate::db::{Database, PriceRow};
+use crate::{log_opportunity, Opportunity, OpportunityRecord, Settings};
+use async_trait::async_trait;
+use eyre::Result;
+use futures::future::join_all;
+use serde::{Deserialize, Serialize};
+use std::sync::Mutex;
+use tracing::error;
+
+/// The kinds of storage prices and opportunities can be recorded to
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum SinkKind {
+    /// Opportunities appended to `output_csv`, or daily files in `output_dir`
+    Csv,
+    /// Prices and opportunities in the `database_path` SQLite database
+    Sqlite,
+}
+
+/// Somewhere prices and opportunities are recorded as they are found
+#[async_trait]
+pub trait Sink: Send + Sync {
+    /// Short name used in log messages
+    fn name(&self) -> &'static str;
+
+    async fn record_price(&self, row: &PriceRow) -> Result<()>;
+
+    async fn record_opportunity(&self, opportunity: &Opportunity) -> Result<()>;
+
+    /// Finishes any writes still queued, at shutdown
+    fn close(&self) {}
+}
+
+/// Opens the configured sinks: those listed in `sinks`, or otherwise every one whose
+/// path is set
+pub fn from_settings(settings: &Settings) -> Result<Vec<Box<dyn Sink>>> {
+    let kinds = settings.sinks.clone().unwrap_or_else(|| {
+        let csv = (settings.output_csv.is_some() || settings.output_dir.is_some()).then_some(SinkKind::Csv);
+        let sqlite = settings.database_path.is_some().then_some(SinkKind::Sqlite);
+        csv.into_iter().chain(sqlite).collect()
+    });
+    let mut sinks: Vec<Box<dyn Sink>> = Vec::new();
+    for kind in kinds {
+        match kind {
+            SinkKind::Csv => {
+                let output = match (&settings.output_dir, &settings.output_csv) {
+                    (Some(dir), _) => CsvOutput::Daily(Box::new(DailyCsv::new(dir, "opportunities"))),
+                    (None, Some(path)) => CsvOutput::File(path.clone()),
+                    (None, None) => unreachable!("validated: the csv sink has a path"),
+                };
+                sinks.push(Box::new(CsvSink { output: Mutex::new(output) }));
+            }
+            SinkKind::Sqlite => {
+                let path = settings.database_path.as_deref().expect("validated: the sqlite sink has a path");
+                sinks.push(Box::new(SqliteSink { database: Mutex::new(Some(Database::open(path)?)) }));
+            }
+        }
+    }
+    Ok(sinks)
+}
+
+/// Records a price in every sink. A sink that fails is logged and skipped, so the
+/// others and the loop carry on.
+pub async fn record_price(sinks: &[Box<dyn Sink>], row: &PriceRow) {
+    let results = join_all(sinks.iter().map(|sink| sink.record_price(row))).await;
+    for (sink, result) in sinks.iter().zip(results) {
+        if let Err(e) = result {
+            error!("Error recording a price to the {} sink: {}", sink.name(), e);
+        }
+    }
+}
+
+/// Records an opportunity in every sink, isolating failures like `record_price`
+pub async fn record_opportunity(sinks: &[Box<dyn Sink>], opportunity: &Opportunity) {
+    let results = join_all(sinks.iter().map(|sink| sink.record_opportunity(opportunity))).await;
+    for (sink, result) in sinks.iter().zip(results) {
+        if let Err(e) = result {
+            error!(pair = %opportunity.pair, "Error recording the opportunity to the {} sink: {}", sink.name(), e);
+        }
+    }
+}
+
+enum CsvOutput {
+    File(String),
+    Daily(Box<DailyCsv>),
+}
+
+/// Writes opportunities as CSV rows. Prices aren't recorded, the database keeps those.
+struct CsvSink {
+    output: Mutex<CsvOutput>,
+}
+
+#[async_trait]
+impl Sink for CsvSink {
+    fn name(&self) -> &'static str {
+        "CSV"
+    }
+
+    async fn record_price(&self, _: &PriceRow) -> Result<()> {
+        Ok(())
+    }
+
+    async fn record_opportunity(&self, opportunity: &Opportunity) -> Result<()> {
+        let record = OpportunityRecord::new(opportunity);
+        match &mut *self.output.lock().unwrap() {
+            CsvOutput::File(path) => log_opportunity(path, &record),
+            CsvOutput::Daily(daily_csv) => daily_csv.write(&record, opportunity.timestamp),
+        }
+    }
+}
+
+/// Queues prices and opportunities for the database's writer thread
+struct SqliteSink {
+    /// Taken when closing, which waits for the writer thread
+    database: Mutex<Option<Database>>,
+}
+
+#[async_trait]
+impl Sink for SqliteSink {
+    fn name(&self) -> &'static str {
+        "SQLite"
+    }
+
+    async fn record_price(&self, row: &PriceRow) -> Result<()> {
+        if let Some(database) = &*self.database.lock().unwrap() {
+            database.record_price(row.clone());
+        }
+        Ok(())
+    }
+
+    async fn record_opportunity(&self, opportunity: &Opportunity) -> Result<()> {
+        if let Some(database) = &*self.database.lock().unwrap() {
+            database.record_opportunity(opportunity);
+        }
+        Ok(())
+    }
+
+    fn close(&self) {
+        if let Some(database) = self.database.lock().unwrap().take() {
+            database.close();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::Arc;
+
+    struct Broken;
+
+    #[async_trait]
+    impl Sink for Broken {
+        fn name(&self) -> &'static str {
+            "broken"
+        }
+
+        async fn record_price(&self, _: &PriceRow) -> Result<()> {
+            Err(eyre::eyre!("disk full"))
+        }
+
+        async fn record_opportunity(&self, _: &Opportunity) -> Result<()> {
+            Err(eyre::eyre!("disk full"))
+        }
+    }
+
+    struct Counting(Arc<AtomicUsize>);
+
+    #[async_trait]
+    impl Sink for Counting {
+        fn name(&self) -> &'static str {
+            "counting"
+        }
+
+        async fn record_price(&self, _: &PriceRow) -> Result<()> {
+            self.0.fetch_add(1, Ordering::SeqCst);
+            Ok(())
+        }
+
+        async fn record_opportunity(&self, _: &Opportunity) -> Result<()> {
+            Ok(())
+        }
+    }
+
+    #[tokio::test]
+    async fn a_failing_sink_does_not_stop_the_others() {
+        let recorded = Arc::new(AtomicUsize::new(0));
+        let sinks: Vec<Box<dyn Sink>> = vec![Box::new(Broken), Box::new(Counting(recorded.clone()))];
+        let row = PriceRow {
+            timestamp: chrono::Utc::now(),
+            block_number: None,
+            dex_name: "QuickSwap".to_string(),
+            pair: "WETH/USDC".to_string(),
+            amount_in: 1.0,
+            amount_out: 3400.0,
+        };
+        record_price(&sinks, &row).await;
+        record_price(&sinks, &row).await;
+        assert_eq!(recorded.load(Ordering::SeqCst), 2);
+    }
+}
@@ -0,0 +1,12 @@
+use crate::error::BotError;
+use async_trait::async_trait;
+use ethers::types::{Address, BlockId, U256};
+
+/// Quotes swaps for one DEX. On-chain routers and quoters implement it today; an
+/// off-chain aggregator (or a stub in tests) only needs to do the same.
+#[async_trait]
+pub trait PriceSource: Send + Sync {
+    /// Quotes the raw amount received for swapping `amount_in` along `path`, at `block`
+    /// if given or else the latest block
+    async fn amount_out(&self, amount_in: U256, path: &[Address], block: Option<BlockId>) -> Result<U256, BotError>;
+}
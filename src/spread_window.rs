@@ -0,0 +1,48 @@
+use std::collections::{HashMap, VecDeque};
+
+/// The last few spreads seen for each pair and trade size, to tell a sustained
+/// dislocation from a one-tick blip
+pub struct SpreadWindows {
+    len: usize,
+    windows: HashMap<(String, u64), VecDeque<f64>>,
+}
+
+impl SpreadWindows {
+    /// Keeps up to `len` spreads per pair and trade size
+    pub fn new(len: usize) -> Self {
+        SpreadWindows {
+            len: len.max(1),
+            windows: HashMap::new(),
+        }
+    }
+
+    /// Adds this tick's spread for the pair and returns the average over its window,
+    /// or over every spread seen so far while the window is still filling
+    pub fn record(&mut self, pair: &str, trade_size: f64, spread_pct: f64) -> f64 {
+        let window = self.windows.entry((pair.to_string(), trade_size.to_bits())).or_default();
+        window.push_back(spread_pct);
+        while window.len() > self.len {
+            window.pop_front();
+        }
+        window.iter().sum::<f64>() / window.len() as f64
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn averages_the_latest_spreads_per_pair() {
+        let mut windows = SpreadWindows::new(3);
+        let mut record = |pair, trade_size, spread_pct| windows.record(pair, trade_size, spread_pct);
+        let close = |average: f64, expected: f64| (average - expected).abs() < 1e-9;
+        assert!(close(record("WETH/USDC", 1.0, 0.3), 0.3));
+        assert!(close(record("WETH/USDC", 1.0, 0.0), 0.15));
+        assert!(close(record("WBTC/USDC", 1.0, 0.9), 0.9));
+        assert!(close(record("WETH/USDC", 1.0, 0.3), 0.2));
+        // The first 0.3 drops out of the window
+        assert!(close(record("WETH/USDC", 1.0, 0.6), 0.3));
+        assert!(close(record("WETH/USDC", 2.0, 0.1), 0.1));
+    }
+}
@@ -0,0 +1,157 @@
+use tracing::info;
+
+/// Running totals for the current run, logged as a summary every few ticks
+#[derive(Debug, Default)]
+pub struct Stats {
+    ticks: u64,
+    fetch_errors: u64,
+    opportunities: u64,
+    best_net_profit: Option<f64>,
+    spread_pct_sum: f64,
+    spreads: u64,
+    /// The widest spreads that didn't clear the thresholds, widest first
+    near_misses: Vec<NearMiss>,
+}
+
+/// A trade that would have been reported had the thresholds been lower
+#[derive(Debug, Clone, PartialEq)]
+pub struct NearMiss {
+    pub pair: String,
+    pub buy_dex: String,
+    pub sell_dex: String,
+    pub trade_size: f64,
+    pub spread_pct: f64,
+    pub net_profit: f64,
+    /// The extra net profit that would have cleared the thresholds
+    pub shortfall: f64,
+    /// The token `net_profit` and `shortfall` are in
+    pub symbol: String,
+}
+
+impl Stats {
+    pub fn record_tick(&mut self) {
+        self.ticks += 1;
+    }
+
+    pub fn ticks(&self) -> u64 {
+        self.ticks
+    }
+
+    pub fn record_fetch_error(&mut self) {
+        self.fetch_errors += 1;
+    }
+
+    /// Records the best spread seen for a pair, as a percentage of its buy price
+    pub fn record_spread(&mut self, spread_pct: f64) {
+        self.spread_pct_sum += spread_pct;
+        self.spreads += 1;
+    }
+
+    /// Keeps the `keep` widest near misses, one per pair, route and trade size, so a
+    /// spread that misses every tick doesn't crowd out the rest
+    pub fn record_near_miss(&mut self, near_miss: NearMiss, keep: usize) {
+        let same_trade = |other: &NearMiss| {
+            other.pair == near_miss.pair
+                && other.buy_dex == near_miss.buy_dex
+                && other.sell_dex == near_miss.sell_dex
+                && other.trade_size == near_miss.trade_size
+        };
+        match self.near_misses.iter().position(same_trade) {
+            Some(i) if self.near_misses[i].spread_pct >= near_miss.spread_pct => return,
+            Some(i) => {
+                self.near_misses.remove(i);
+            }
+            None => {}
+        }
+        self.near_misses.push(near_miss);
+        self.near_misses.sort_by(|a, b| b.spread_pct.total_cmp(&a.spread_pct));
+        self.near_misses.truncate(keep);
+    }
+
+    pub fn record_opportunity(&mut self, net_profit: f64) {
+        self.opportunities += 1;
+        if self.best_net_profit.is_none_or(|best| net_profit > best) {
+            self.best_net_profit = Some(net_profit);
+        }
+    }
+
+    pub fn average_spread_pct(&self) -> Option<f64> {
+        (self.spreads > 0).then(|| self.spread_pct_sum / self.spreads as f64)
+    }
+
+    /// Logs the totals, with profits rounded to `decimals` places
+    pub fn log_summary(&self, decimals: usize) {
+        info!("=== Summary after {} ticks ===", self.ticks);
+        info!("  - Fetch errors: {}", self.fetch_errors);
+        info!("  - Opportunities found: {}", self.opportunities);
+        match self.best_net_profit {
+            Some(profit) => info!("  - Best net profit: {:.*}", decimals, profit),
+            None => info!("  - Best net profit: n/a"),
+        }
+        match self.average_spread_pct() {
+            Some(spread) => info!("  - Average spread: {:.4}%", spread),
+            None => info!("  - Average spread: n/a"),
+        }
+        if !self.near_misses.is_empty() {
+            info!("  - Widest spreads below the thresholds:");
+        }
+        for miss in &self.near_misses {
+            info!(
+                "    {} {} {} -> {}: spread {:.4}%, net profit {:.*} {}, {:.*} short",
+                miss.trade_size,
+                miss.pair,
+                miss.buy_dex,
+                miss.sell_dex,
+                miss.spread_pct,
+                decimals,
+                miss.net_profit,
+                miss.symbol,
+                decimals,
+                miss.shortfall
+            );
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn tracks_best_profit_and_average_spread() {
+        let mut stats = Stats::default();
+        assert_eq!(stats.average_spread_pct(), None);
+
+        stats.record_spread(0.2);
+        stats.record_spread(0.4);
+        stats.record_opportunity(3.0);
+        stats.record_opportunity(1.0);
+
+        assert!((stats.average_spread_pct().unwrap() - 0.3).abs() < 1e-9);
+        assert_eq!(stats.best_net_profit, Some(3.0));
+        assert_eq!(stats.opportunities, 2);
+    }
+
+    #[test]
+    fn keeps_the_widest_near_miss_per_trade() {
+        let miss = |pair: &str, spread_pct| NearMiss {
+            pair: pair.to_string(),
+            buy_dex: "QuickSwap".to_string(),
+            sell_dex: "SushiSwap".to_string(),
+            trade_size: 1.0,
+            spread_pct,
+            net_profit: -1.0,
+            shortfall: 2.0,
+            symbol: "USDC".to_string(),
+        };
+        let mut stats = Stats::default();
+        stats.record_near_miss(miss("WETH/USDC", 0.2), 2);
+        stats.record_near_miss(miss("WETH/USDC", 0.3), 2);
+        stats.record_near_miss(miss("WETH/USDC", 0.1), 2);
+        stats.record_near_miss(miss("WBTC/USDC", 0.25), 2);
+        stats.record_near_miss(miss("WMATIC/USDC", 0.05), 2);
+
+        let kept: Vec<_> = stats.near_misses.iter().map(|miss| (miss.pair.as_str(), miss.spread_pct)).collect();
+        assert_eq!(kept, [("WETH/USDC", 0.3), ("WBTC/USDC", 0.25)]);
+    }
+}
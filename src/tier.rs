@@ -0,0 +1,57 @@
+use serde::{Deserialize, Serialize};
+use std::fmt;
+
+/// How loudly an opportunity is reported, by how much it would make
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Tier {
+    /// Cleared the thresholds, but not `tiers.notable`
+    #[default]
+    Info,
+    Notable,
+    Strong,
+}
+
+impl fmt::Display for Tier {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Tier::Info => "info",
+            Tier::Notable => "notable",
+            Tier::Strong => "strong",
+        })
+    }
+}
+
+/// The `[tiers]` settings: the net profit an opportunity needs to be `notable` or
+/// `strong`, in the same token as `minimum_profit_threshold`
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct Tiers {
+    pub notable: f64,
+    pub strong: f64,
+}
+
+impl Tiers {
+    pub fn classify(&self, net_profit: f64) -> Tier {
+        if net_profit >= self.strong {
+            Tier::Strong
+        } else if net_profit >= self.notable {
+            Tier::Notable
+        } else {
+            Tier::Info
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn classifies_by_net_profit() {
+        let tiers = Tiers { notable: 10.0, strong: 50.0 };
+        assert_eq!(tiers.classify(9.99), Tier::Info);
+        assert_eq!(tiers.classify(10.0), Tier::Notable);
+        assert_eq!(tiers.classify(50.0), Tier::Strong);
+        assert!(Tier::Strong > Tier::Notable && Tier::Notable > Tier::Info);
+    }
+}
@@ -0,0 +1,74 @@
+use crate::clock::Clock;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+use tracing::{error, info};
+
+/// When the main loop last completed a tick, in Unix seconds. Shared with the watchdog.
+#[derive(Debug, Clone)]
+pub struct Heartbeat(Arc<AtomicU64>);
+
+impl Heartbeat {
+    pub fn new(now: u64) -> Self {
+        Heartbeat(Arc::new(AtomicU64::new(now)))
+    }
+
+    pub fn beat(&self, now: u64) {
+        self.0.store(now, Ordering::Relaxed);
+    }
+
+    /// Treats the loop as alive until `until`, e.g. while it deliberately backs off
+    pub fn excuse_until(&self, until: u64) {
+        self.0.fetch_max(until, Ordering::Relaxed);
+    }
+
+    /// Whether no tick has completed in more than `timeout`
+    pub fn is_stale(&self, now: u64, timeout: Duration) -> bool {
+        now.saturating_sub(self.0.load(Ordering::Relaxed)) > timeout.as_secs()
+    }
+}
+
+/// Checks the heartbeat in the background and logs an error whenever the main loop
+/// stops completing ticks for longer than `timeout`, exiting with status 1 if `exit`
+/// is set so an orchestrator can restart the bot
+pub fn spawn(heartbeat: Heartbeat, timeout: Duration, exit: bool, clock: Arc<dyn Clock>) {
+    tokio::spawn(async move {
+        let mut stalled = false;
+        let mut checks = tokio::time::interval((timeout / 4).max(Duration::from_secs(1)));
+        loop {
+            checks.tick().await;
+            match (heartbeat.is_stale(clock.unix_seconds(), timeout), stalled) {
+                (true, false) => {
+                    error!("!!! Main loop hasn't completed a check in over {}s, it may be stuck !!!", timeout.as_secs());
+                    if exit {
+                        std::process::exit(1);
+                    }
+                    stalled = true;
+                }
+                (false, true) => {
+                    info!("Main loop is completing checks again.");
+                    stalled = false;
+                }
+                _ => {}
+            }
+        }
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn goes_stale_after_the_timeout_unless_excused() {
+        let timeout = Duration::from_secs(60);
+        let heartbeat = Heartbeat::new(1_000);
+        assert!(!heartbeat.is_stale(1_060, timeout));
+        assert!(heartbeat.is_stale(1_061, timeout));
+
+        heartbeat.beat(1_061);
+        heartbeat.excuse_until(1_361);
+        assert!(!heartbeat.is_stale(1_400, timeout));
+        assert!(heartbeat.is_stale(1_422, timeout));
+    }
+}